@@ -0,0 +1,210 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::Value as JsonValue;
+
+use crate::core::{Answer, Param, ParamKind};
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+/// Answers of a generation, read by `--answers` and written by `--save-answers`, eg:
+///
+/// ```toml
+/// name = "Hello World"
+/// version = "0.1.0"
+/// author_name = "Robin"
+/// with_serde = true
+/// ```
+///
+/// Files with `.json` extension are read and written as JSON object.
+#[derive(Debug, Default, PartialEq)]
+pub struct AnswersFile {
+    pub values: Vec<(String, JsonValue)>,
+}
+
+impl Serialize for AnswersFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // keep the asking order
+        let mut map = serializer.serialize_map(Some(self.values.len()))?;
+        for (k, v) in self.values.iter() {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl AnswersFile {
+    /// Answers to record, secret params are left out.
+    /// Values of typed params are written as booleans, numbers and arrays.
+    pub fn from_answers(answers: &[Answer]) -> Self {
+        let values = answers
+            .iter()
+            .filter(|a| !a.secret)
+            .map(|a| {
+                let param = Param {
+                    kind: a.kind.clone(),
+                    ..Param::new(&a.key, &a.value)
+                };
+                let value = match a.kind {
+                    ParamKind::String | ParamKind::Options => JsonValue::String(a.value.to_owned()),
+                    _ => param.to_json(&a.value),
+                };
+                (a.key.to_owned(), value)
+            })
+            .collect();
+        AnswersFile { values }
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let f = fs::read(&path)?;
+        let rv = String::from_utf8_lossy(f.as_slice());
+        let values: BTreeMap<String, JsonValue> = if is_json(path.as_ref()) {
+            serde_json::from_str(&rv).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        } else {
+            toml::from_str(&rv).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        };
+        let is_scalar = |v: &JsonValue| v.is_string() || v.is_boolean() || v.is_number();
+        for (k, v) in values.iter() {
+            let valid = match v {
+                JsonValue::Array(items) => items.iter().all(is_scalar),
+                v => is_scalar(v),
+            };
+            if !valid {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unsupported value of `{}`: {}", k, v),
+                ))?;
+            }
+        }
+        Ok(AnswersFile {
+            values: values.into_iter().collect(),
+        })
+    }
+
+    /// Read answers file, errors mention the file path.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(&path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("invalid answers file `{}`: {}", path.as_ref().display(), e),
+            )
+        })
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let data = if is_json(path.as_ref()) {
+            serde_json::to_string_pretty(self).map_err(io::Error::other)? + "\n"
+        } else {
+            toml::to_string(self).map_err(io::Error::other)?
+        };
+        fs::write(path, data)
+    }
+
+    /// Answers as preset params.
+    pub fn params(&self) -> Vec<Param> {
+        self.values
+            .iter()
+            .map(|(k, v)| Param::new(k, scalar_string(v)))
+            .collect()
+    }
+}
+
+/// Value as param value, array items are separated by comma.
+fn scalar_string(v: &JsonValue) -> String {
+    match v {
+        JsonValue::String(s) => s.to_owned(),
+        JsonValue::Array(items) => items
+            .iter()
+            .map(scalar_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        v => v.to_string(),
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::AnswerSource;
+    use crate::util;
+    use std::env;
+
+    fn answer(key: &str, value: &str, kind: ParamKind) -> Answer {
+        Answer {
+            key: key.to_string(),
+            value: value.to_string(),
+            kind,
+            secret: false,
+            source: AnswerSource::Prompt,
+        }
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let mut token = answer("token", "secret", ParamKind::String);
+        token.secret = true;
+        let answers = AnswersFile::from_answers(&[
+            answer("name", "Hello World", ParamKind::String),
+            answer("version", "0.1.0", ParamKind::String),
+            answer("with_serde", "true", ParamKind::Bool),
+            token,
+            answer("license", "MIT", ParamKind::Options),
+            answer("workers", "4", ParamKind::Int),
+            answer("features", "auth, admin", ParamKind::List),
+        ]);
+
+        for ext in ["toml", "json"] {
+            let path = env::temp_dir().join(format!(
+                "reframe_test_answers_{}.{}",
+                util::get_current_time_millis(),
+                ext
+            ));
+            answers.write(&path).unwrap();
+            let text = fs::read_to_string(&path).unwrap();
+            if ext == "toml" {
+                assert!(text.starts_with("name = \"Hello World\"\nversion = \"0.1.0\"\n"));
+                assert!(text.contains("with_serde = true\n"));
+                assert!(text.contains("workers = 4\n"));
+                assert!(text.contains("features = [\"auth\", \"admin\"]\n"));
+                assert!(!text.contains("secret"));
+            } else {
+                assert!(text.contains("\"with_serde\": true"));
+            }
+
+            let params = AnswersFile::load(&path).unwrap().params();
+            let value = |key: &str| {
+                params
+                    .iter()
+                    .find(|p| p.key == key)
+                    .and_then(|p| p.value.clone())
+            };
+            assert_eq!(params.len(), 6);
+            assert_eq!(value("name").as_deref(), Some("Hello World"));
+            assert_eq!(value("with_serde").as_deref(), Some("true"));
+            assert_eq!(value("license").as_deref(), Some("MIT"));
+            assert_eq!(value("workers").as_deref(), Some("4"));
+            assert_eq!(value("features").as_deref(), Some("auth, admin"));
+
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let path = env::temp_dir().join(format!(
+            "reframe_test_answers_invalid_{}.toml",
+            util::get_current_time_millis()
+        ));
+        fs::write(
+            &path,
+            "name = \"x\"\nlist = [1, \"a\"]\n[nested]\nkey = 1\n",
+        )
+        .unwrap();
+        let err = AnswersFile::load(&path).unwrap_err();
+        assert!(err.to_string().contains("unsupported value of `nested`"));
+        let _ = fs::remove_file(&path);
+    }
+}
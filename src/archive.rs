@@ -0,0 +1,574 @@
+use log::debug;
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+/// Limits applied when extracting archive, guard against archive bombs.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Maximum total size of extracted files in bytes.
+    pub max_total_size: u64,
+    /// Maximum number of entries in archive.
+    pub max_files: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_total_size: 512 * 1024 * 1024,
+            max_files: 20_000,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExtractError {
+    Io(io::Error),
+    /// Archive cannot be read.
+    Corrupt(String),
+    /// Entry path escapes the output dir.
+    UnsafePath(String),
+    /// Symlink pointing outside the output dir.
+    UnsafeSymlink {
+        path: String,
+        target: String,
+    },
+    TooManyFiles(usize),
+    TooLarge(u64),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Io(e) => write!(f, "{}", e),
+            ExtractError::Corrupt(e) => write!(f, "corrupt archive: {}", e),
+            ExtractError::UnsafePath(path) => {
+                write!(f, "archive entry `{}` escapes the output dir", path)
+            }
+            ExtractError::UnsafeSymlink { path, target } => write!(
+                f,
+                "archive symlink `{}` points outside the output dir: `{}`",
+                path, target
+            ),
+            ExtractError::TooManyFiles(max) => {
+                write!(f, "archive contains more than {} entries", max)
+            }
+            ExtractError::TooLarge(max) => {
+                write!(f, "archive extracts to more than {} bytes", max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl From<io::Error> for ExtractError {
+    fn from(e: io::Error) -> Self {
+        ExtractError::Io(e)
+    }
+}
+
+impl From<ExtractError> for io::Error {
+    fn from(e: ExtractError) -> Self {
+        match e {
+            ExtractError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, ExtractError>;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Writes archive entries into output dir, making sure nothing is written outside of it.
+struct Extractor<'a> {
+    out_dir: PathBuf,
+    limits: &'a Limits,
+    files: usize,
+    total_size: u64,
+}
+
+impl<'a> Extractor<'a> {
+    fn new(out_dir: &Path, limits: &'a Limits) -> Result<Self> {
+        fs::create_dir_all(out_dir)?;
+        Ok(Extractor {
+            out_dir: out_dir.canonicalize()?,
+            limits,
+            files: 0,
+            total_size: 0,
+        })
+    }
+
+    /// Output path of entry `name`, `None` for the archive root itself.
+    fn entry_path(&self, name: &str) -> Result<Option<PathBuf>> {
+        let mut path = PathBuf::new();
+        for c in Path::new(name).components() {
+            match c {
+                Component::Normal(c) => path.push(c),
+                Component::CurDir => (),
+                _ => return Err(ExtractError::UnsafePath(name.to_string())),
+            }
+        }
+        if path.as_os_str().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(self.out_dir.join(path)))
+    }
+
+    fn count_entry(&mut self) -> Result<()> {
+        self.files += 1;
+        if self.files > self.limits.max_files {
+            return Err(ExtractError::TooManyFiles(self.limits.max_files));
+        }
+        Ok(())
+    }
+
+    fn is_inside(&self, path: &Path) -> bool {
+        path.starts_with(&self.out_dir)
+    }
+
+    /// Create dir, refusing to create it through symlink pointing outside.
+    fn ensure_dir(&self, dir: &Path, name: &str) -> Result<()> {
+        let mut existing = dir;
+        while !existing.exists() {
+            existing = match existing.parent() {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+        if !self.is_inside(&existing.canonicalize()?) {
+            return Err(ExtractError::UnsafePath(name.to_string()));
+        }
+        fs::create_dir_all(dir)?;
+        Ok(())
+    }
+
+    fn create_dir(&mut self, name: &str) -> Result<()> {
+        self.count_entry()?;
+        if let Some(path) = self.entry_path(name)? {
+            debug!("extracting dir `{}`", path.display());
+            self.ensure_dir(&path, name)?;
+        }
+        Ok(())
+    }
+
+    fn write_file<R: Read>(&mut self, name: &str, reader: R, mode: Option<u32>) -> Result<()> {
+        self.count_entry()?;
+        let path = match self.entry_path(name)? {
+            Some(path) => path,
+            None => return Err(ExtractError::UnsafePath(name.to_string())),
+        };
+        if let Some(parent) = path.parent() {
+            self.ensure_dir(parent, name)?;
+        }
+        // never write through existing symlink
+        if let Ok(meta) = fs::symlink_metadata(&path) {
+            if meta.file_type().is_symlink() {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        debug!("extracting file `{}`", path.display());
+
+        let remaining = self.limits.max_total_size.saturating_sub(self.total_size);
+        let mut fout = File::create(&path)?;
+        let written = io::copy(&mut reader.take(remaining + 1), &mut fout)?;
+        if written > remaining {
+            drop(fout);
+            let _ = fs::remove_file(&path);
+            return Err(ExtractError::TooLarge(self.limits.max_total_size));
+        }
+        self.total_size += written;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if let Some(mode) = mode {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        Ok(())
+    }
+
+    fn create_symlink(&mut self, name: &str, target: &str) -> Result<()> {
+        self.count_entry()?;
+        let unsafe_symlink = || ExtractError::UnsafeSymlink {
+            path: name.to_string(),
+            target: target.to_string(),
+        };
+        let path = match self.entry_path(name)? {
+            Some(path) => path,
+            None => return Err(ExtractError::UnsafePath(name.to_string())),
+        };
+
+        // resolve target lexically relative to the link location
+        let mut depth = path
+            .strip_prefix(&self.out_dir)
+            .map(|p| p.components().count())
+            .unwrap_or(0) as isize
+            - 1;
+        for c in Path::new(target).components() {
+            match c {
+                Component::Normal(_) => depth += 1,
+                Component::CurDir => (),
+                Component::ParentDir => {
+                    depth -= 1;
+                    if depth < 0 {
+                        return Err(unsafe_symlink());
+                    }
+                }
+                _ => return Err(unsafe_symlink()),
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            self.ensure_dir(parent, name)?;
+        }
+
+        debug!("extracting symlink `{}` -> `{}`", path.display(), target);
+
+        #[cfg(unix)]
+        {
+            if fs::symlink_metadata(&path).is_ok() {
+                fs::remove_file(&path)?;
+            }
+            std::os::unix::fs::symlink(target, &path)?;
+            if let Ok(resolved) = path.canonicalize() {
+                if !self.is_inside(&resolved) {
+                    fs::remove_file(&path)?;
+                    return Err(unsafe_symlink());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Symlinks created before their targets may resolve outside
+    /// once the whole archive extracted, check them all again.
+    fn verify_symlinks(&self, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let meta = fs::symlink_metadata(&path)?;
+            if meta.file_type().is_symlink() {
+                if let Ok(resolved) = path.canonicalize() {
+                    if !self.is_inside(&resolved) {
+                        let target = fs::read_link(&path)?;
+                        fs::remove_file(&path)?;
+                        return Err(ExtractError::UnsafeSymlink {
+                            path: format!("{}", path.display()),
+                            target: format!("{}", target.display()),
+                        });
+                    }
+                }
+            } else if meta.is_dir() {
+                self.verify_symlinks(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn extract_zip(zip_path: &Path, out_dir: &Path, limits: &Limits) -> Result<()> {
+    let fin = File::open(zip_path)?;
+    let mut archive = ZipArchive::new(fin).map_err(|e| ExtractError::Corrupt(format!("{}", e)))?;
+    let mut ex = Extractor::new(out_dir, limits)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| ExtractError::Corrupt(format!("entry {}: {}", i, e)))?;
+        let name = file.name().to_string();
+        let mode = file.unix_mode();
+
+        if file.is_dir() {
+            ex.create_dir(&name)?;
+        } else if mode.map(|m| m & S_IFMT) == Some(S_IFLNK) {
+            let mut target = String::new();
+            file.by_ref().take(4096).read_to_string(&mut target)?;
+            ex.create_symlink(&name, &target)?;
+        } else {
+            if file.size() > limits.max_total_size {
+                return Err(ExtractError::TooLarge(limits.max_total_size));
+            }
+            ex.write_file(&name, &mut file, mode)?;
+        }
+    }
+
+    ex.verify_symlinks(&ex.out_dir)
+}
+
+fn extract_tar<R: Read>(reader: R, out_dir: &Path, limits: &Limits) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    let mut ex = Extractor::new(out_dir, limits)?;
+
+    let corrupt = |e: io::Error| ExtractError::Corrupt(format!("{}", e));
+
+    for entry in archive.entries().map_err(corrupt)? {
+        let mut entry = entry.map_err(corrupt)?;
+        let name = format!("{}", entry.path().map_err(corrupt)?.display());
+        let kind = entry.header().entry_type();
+
+        if kind.is_dir() {
+            ex.create_dir(&name)?;
+        } else if kind.is_symlink() {
+            let target = entry
+                .link_name()
+                .map_err(corrupt)?
+                .map(|a| format!("{}", a.display()))
+                .unwrap_or_default();
+            ex.create_symlink(&name, &target)?;
+        } else if kind.is_hard_link() {
+            let target = entry
+                .link_name()
+                .map_err(corrupt)?
+                .map(|a| format!("{}", a.display()))
+                .unwrap_or_default();
+            let src = ex
+                .entry_path(&target)?
+                .filter(|src| fs::symlink_metadata(src).is_ok_and(|m| m.is_file()))
+                .ok_or_else(|| ExtractError::UnsafePath(target.to_owned()))?;
+            ex.write_file(&name, File::open(src)?, entry.header().mode().ok())?;
+        } else if kind.is_file() || kind.is_contiguous() {
+            let mode = entry.header().mode().ok();
+            ex.write_file(&name, &mut entry, mode)?;
+        } else {
+            debug!("skip unsupported entry `{}` ({:?})", name, kind);
+        }
+    }
+
+    ex.verify_symlinks(&ex.out_dir)
+}
+
+/// Archive formats supported by [`extract`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    TarGz,
+    Tar,
+}
+
+impl Format {
+    /// Format by file name extension.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.split('?').next().unwrap_or(name).to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// Format by magic bytes at the start of the file, falls back to the file extension.
+    pub fn detect<P: AsRef<Path>>(path: P) -> io::Result<Option<Self>> {
+        let mut head = Vec::with_capacity(512);
+        File::open(&path)?.take(512).read_to_end(&mut head)?;
+
+        if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            Ok(Some(Format::Zip))
+        } else if head.starts_with(&[0x1f, 0x8b]) {
+            Ok(Some(Format::TarGz))
+        } else if head.len() >= 262 && &head[257..262] == b"ustar" {
+            Ok(Some(Format::Tar))
+        } else {
+            Ok(Self::from_name(&path.as_ref().to_string_lossy()))
+        }
+    }
+}
+
+/// Extract zip, tar.gz or tar archive into `out_dir` within `limits`,
+/// format is detected by magic bytes or file extension.
+pub fn extract<P: AsRef<Path>, Q: AsRef<Path>>(
+    archive_path: P,
+    out_dir: Q,
+    limits: &Limits,
+) -> io::Result<()> {
+    let (archive_path, out_dir) = (archive_path.as_ref(), out_dir.as_ref());
+
+    debug!(
+        "extracting `{}` to `{}` ...",
+        archive_path.display(),
+        out_dir.display()
+    );
+
+    let rv = match Format::detect(archive_path)? {
+        Some(Format::Zip) => extract_zip(archive_path, out_dir, limits),
+        Some(Format::TarGz) => extract_tar(
+            flate2::read::GzDecoder::new(File::open(archive_path)?),
+            out_dir,
+            limits,
+        ),
+        Some(Format::Tar) => extract_tar(File::open(archive_path)?, out_dir, limits),
+        None => Err(ExtractError::Corrupt("unknown archive format".to_string())),
+    };
+
+    rv.map_err(|e| {
+        let e = io::Error::from(e);
+        io::Error::new(
+            e.kind(),
+            format!("cannot extract `{}`: {}", archive_path.display(), e),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::make_zip, util};
+    use std::{env, io::Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "reframe_test_archive_{}_{}",
+            name,
+            util::get_current_time_millis()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn extract_bytes(dir: &Path, name: &str, data: &[u8], limits: &Limits) -> io::Result<()> {
+        let archive = dir.join(name);
+        fs::write(&archive, data).unwrap();
+        extract(&archive, dir.join("out"), limits)
+    }
+
+    fn invalid_data_msg(rv: io::Result<()>) -> String {
+        let err = rv.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        err.to_string()
+    }
+
+    /// Zip with symlink entry, the zip writer can't make symlinks so the
+    /// external attributes of the central directory entry are patched.
+    fn make_zip_with_symlink(name: &str, target: &str) -> Vec<u8> {
+        let mut data = make_zip(&[(name, target)]);
+        let sig = [0x50, 0x4b, 0x01, 0x02];
+        let pos = data.windows(4).position(|w| w == sig).unwrap();
+        let attrs = (S_IFLNK | 0o777) << 16;
+        data[pos + 38..pos + 42].copy_from_slice(&attrs.to_le_bytes());
+        data
+    }
+
+    fn make_tar_gz_with_symlink(name: &str, target: &str) -> Vec<u8> {
+        let gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        let mut tw = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        tw.append_link(&mut header, name, target).unwrap();
+        tw.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_extract_zip() {
+        let dir = temp_dir("ok");
+        let zip = make_zip(&[("t/Reframe.toml", "x"), ("t/src/main.rs", "y")]);
+        extract_bytes(&dir, "a.zip", &zip, &Limits::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("out/t/src/main.rs")).unwrap(),
+            "y"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_reject_zip_slip() {
+        let dir = temp_dir("slip");
+        let zip = make_zip(&[("../evil.txt", "x")]);
+        let msg = invalid_data_msg(extract_bytes(&dir, "a.zip", &zip, &Limits::default()));
+        assert!(msg.contains("escapes the output dir"));
+        assert!(!dir.join("evil.txt").exists());
+
+        let zip = make_zip(&[("/tmp/evil.txt", "x")]);
+        invalid_data_msg(extract_bytes(&dir, "b.zip", &zip, &Limits::default()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reject_symlink_outside() {
+        let dir = temp_dir("symlink");
+        let zip = make_zip_with_symlink("t/link", "../../secret");
+        let msg = invalid_data_msg(extract_bytes(&dir, "a.zip", &zip, &Limits::default()));
+        assert!(msg.contains("points outside"));
+
+        let zip = make_zip_with_symlink("t/link", "/etc/passwd");
+        invalid_data_msg(extract_bytes(&dir, "b.zip", &zip, &Limits::default()));
+
+        let tgz = make_tar_gz_with_symlink("t/link", "../../secret");
+        invalid_data_msg(extract_bytes(&dir, "c.tar.gz", &tgz, &Limits::default()));
+
+        // symlink inside is fine
+        let zip = make_zip_with_symlink("t/link", "../t/other");
+        extract_bytes(&dir, "d.zip", &zip, &Limits::default()).unwrap();
+        assert!(fs::symlink_metadata(dir.join("out/t/link"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_limits() {
+        let dir = temp_dir("limits");
+
+        // highly compressible content, 1 MB of zeros
+        let mut zw = ZipWriter::new(io::Cursor::new(vec![]));
+        zw.start_file("bomb.txt", FileOptions::default()).unwrap();
+        zw.write_all(&vec![0; 1024 * 1024]).unwrap();
+        let bomb = zw.finish().unwrap().into_inner();
+        let limits = Limits {
+            max_total_size: 64 * 1024,
+            ..Default::default()
+        };
+        let msg = invalid_data_msg(extract_bytes(&dir, "a.zip", &bomb, &limits));
+        assert!(msg.contains("more than 65536 bytes"));
+
+        let zip = make_zip(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        let limits = Limits {
+            max_files: 2,
+            ..Default::default()
+        };
+        let msg = invalid_data_msg(extract_bytes(&dir, "b.zip", &zip, &limits));
+        assert!(msg.contains("more than 2 entries"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_corrupt_archive() {
+        let dir = temp_dir("corrupt");
+        let msg = invalid_data_msg(extract_bytes(
+            &dir,
+            "a.zip",
+            b"<html>Not Found</html>",
+            &Limits::default(),
+        ));
+        assert!(msg.contains("corrupt archive"));
+
+        assert!(extract_bytes(&dir, "b.tar.gz", b"garbage", &Limits::default()).is_err());
+
+        let err =
+            extract(dir.join("missing.zip"), dir.join("out"), &Limits::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
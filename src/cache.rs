@@ -0,0 +1,222 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::util::Validators;
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+const ENTRY_FILE_NAME: &str = "reframe-cache.json";
+
+/// Dir name inside cache entry where the source is placed.
+pub const SOURCE_DIR_NAME: &str = "src";
+
+/// Metadata of a cached source.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheEntry {
+    pub provider: String,
+    pub source: String,
+    pub reference: Option<String>,
+    pub url: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// SHA-256 digest of the downloaded archive.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    pub fetched_at: String,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl CacheEntry {
+    pub fn new(provider: &str, source: &str, reference: Option<&str>) -> Self {
+        CacheEntry {
+            provider: provider.to_string(),
+            source: source.to_string(),
+            reference: reference.map(|a| a.to_string()),
+            url: None,
+            etag: None,
+            last_modified: None,
+            sha256: None,
+            fetched_at: Utc::now().to_rfc3339(),
+            path: PathBuf::new(),
+        }
+    }
+
+    pub fn sha256_matches(&self, sha256: &str) -> bool {
+        self.sha256
+            .as_deref()
+            .is_some_and(|a| a.eq_ignore_ascii_case(sha256))
+    }
+
+    pub fn validators(&self) -> Validators {
+        Validators {
+            etag: self.etag.clone(),
+            last_modified: self.last_modified.clone(),
+        }
+    }
+}
+
+/// Persistent cache of fetched sources, keyed by source and reference.
+pub struct Cache {
+    root: PathBuf,
+}
+
+impl Cache {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Cache {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Cache in the user cache dir, eg: `~/.cache/reframe` on Linux,
+    /// can be overridden by `REFRAME_CACHE_DIR` env var.
+    pub fn user_default() -> Self {
+        if let Ok(dir) = env::var("REFRAME_CACHE_DIR") {
+            return Self::new(dir);
+        }
+        let root = dirs::cache_dir()
+            .unwrap_or_else(env::temp_dir)
+            .join("reframe");
+        Self::new(root)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Dir of cache entry for `location` of `provider` at `reference`.
+    pub fn entry_dir(&self, provider: &str, location: &str, reference: Option<&str>) -> PathBuf {
+        self.root.join(provider).join(format!(
+            "{}@{}",
+            sanitize(location),
+            sanitize(reference.unwrap_or("default"))
+        ))
+    }
+
+    pub fn read_entry<P: AsRef<Path>>(entry_dir: P) -> Option<CacheEntry> {
+        let data = fs::read(entry_dir.as_ref().join(ENTRY_FILE_NAME)).ok()?;
+        let mut entry: CacheEntry = serde_json::from_slice(&data).ok()?;
+        entry.path = entry_dir.as_ref().to_path_buf();
+        Some(entry)
+    }
+
+    pub fn write_entry<P: AsRef<Path>>(entry_dir: P, entry: &CacheEntry) -> io::Result<()> {
+        fs::create_dir_all(&entry_dir)?;
+        let data = serde_json::to_vec_pretty(entry).map_err(io::Error::other)?;
+        fs::write(entry_dir.as_ref().join(ENTRY_FILE_NAME), data)
+    }
+
+    /// List all cached sources.
+    pub fn list(&self) -> io::Result<Vec<CacheEntry>> {
+        let mut entries = vec![];
+        if !self.root.exists() {
+            return Ok(entries);
+        }
+        for provider_dir in fs::read_dir(&self.root)? {
+            let provider_dir = provider_dir?.path();
+            if !provider_dir.is_dir() {
+                continue;
+            }
+            for entry_dir in fs::read_dir(&provider_dir)? {
+                if let Some(entry) = Self::read_entry(entry_dir?.path()) {
+                    entries.push(entry);
+                }
+            }
+        }
+        entries.sort_by(|a, b| (&a.source, &a.reference).cmp(&(&b.source, &b.reference)));
+        Ok(entries)
+    }
+
+    /// Remove all cached sources, returns the number of entries removed.
+    /// Only the entry dirs are removed, the root may be any dir set by the user.
+    pub fn clean(&self) -> io::Result<usize> {
+        let entries = self.list()?;
+        for entry in entries.iter() {
+            fs::remove_dir_all(&entry.path)?;
+            // provider dir, kept when not empty
+            if let Some(parent) = entry.path.parent() {
+                let _ = fs::remove_dir(parent);
+            }
+        }
+        Ok(entries.len())
+    }
+}
+
+/// File name safe form of `s`, other than ASCII alphanumerics, `.`, `-` and `_`
+/// are percent-escaped so different `s` never share a name.
+pub(crate) fn sanitize(s: &str) -> String {
+    s.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b == b'.' || b == b'-' || b == b'_' {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    #[test]
+    fn test_entry_dir() {
+        let cache = Cache::new("/tmp/cache");
+        assert_eq!(
+            cache.entry_dir("github", "github.com/anvie/basic-rust.rf", None),
+            Path::new("/tmp/cache/github/github.com%2Fanvie%2Fbasic-rust.rf@default")
+        );
+        assert_eq!(
+            cache.entry_dir("gitlab", "gitlab.com/group/repo", Some("feature/x")),
+            Path::new("/tmp/cache/gitlab/gitlab.com%2Fgroup%2Frepo@feature%2Fx")
+        );
+        for (a, b) in [
+            (("a/b-c", Some("v1")), ("a_b-c", Some("v1"))),
+            (("repo", Some("feature/x")), ("repo", Some("feature_x"))),
+            (("a@b", None), ("a", Some("b@default"))),
+            (("ü", None), ("%C3%BC", None)),
+        ] {
+            assert_ne!(
+                cache.entry_dir("git", a.0, a.1),
+                cache.entry_dir("git", b.0, b.1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_list_and_clean() {
+        let root = env::temp_dir().join(format!(
+            "reframe_test_cache_{}",
+            util::get_current_time_millis()
+        ));
+        let cache = Cache::new(&root);
+        assert!(cache.list().unwrap().is_empty());
+
+        let mut entry = CacheEntry::new("gitea", "git.example.com/owner/repo", Some("v1"));
+        entry.etag = Some("\"abc\"".to_string());
+        let dir = cache.entry_dir("gitea", "git.example.com/owner/repo", Some("v1"));
+        Cache::write_entry(&dir, &entry).unwrap();
+
+        let entries = cache.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source, "git.example.com/owner/repo");
+        assert_eq!(entries[0].reference.as_deref(), Some("v1"));
+        assert_eq!(entries[0].validators().etag.as_deref(), Some("\"abc\""));
+        assert_eq!(entries[0].path, dir);
+
+        // anything else in the root is left alone
+        fs::write(root.join("notes.txt"), "keep").unwrap();
+        assert_eq!(cache.clean().unwrap(), 1);
+        assert!(!dir.exists());
+        assert!(!root.join("gitea").exists());
+        assert!(root.join("notes.txt").exists());
+        assert!(cache.list().unwrap().is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
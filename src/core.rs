@@ -7,9 +7,10 @@ use lazy_static::lazy_static;
 use log::{debug, error, trace};
 use regex::Regex;
 use rustyline::Editor;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
+use crate::expr::Expr;
 use crate::util;
 
 use std::{
@@ -34,26 +35,26 @@ pub struct Config {
     pub post_generate: Vec<PostGenerateOp>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Present {
     pub path: String,
     #[serde(rename = "if")]
     pub ifcond: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PostGenerateOp {
     pub make_executable: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ReframeConfig {
     pub name: String,
     pub author: String,
     pub min_version: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ProjectConfig {
     pub name: String,
     #[serde(default = "HashMap::new")]
@@ -62,33 +63,233 @@ pub struct ProjectConfig {
     pub ignore_dirs: Option<Vec<String>>,
     pub ignore_files: Option<Vec<String>>,
     pub finish_text: Option<String>,
+    /// Rules of the project name, eg: `name_rules = { pattern = "^[a-z]" }`.
+    #[serde(default, skip_serializing_if = "Validation::is_empty")]
+    pub name_rules: Validation,
+}
+
+impl Config {
+    /// Params declared in `[[param]]` sections, without values.
+    pub fn params(&self) -> io::Result<Vec<Param>> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut params = vec![];
+        for item in &self.param {
+            if let JsonValue::Object(o) = &item {
+                for (k, item) in o {
+                    let ask = get_string(item, "ask", k)?;
+                    let dflt = get_string_option(item, "default");
+                    let mut options: Vec<String> = vec![];
+                    if let Some(JsonValue::Array(values)) = item.get("options") {
+                        options = values
+                            .iter()
+                            .map(|a| match a {
+                                JsonValue::String(a_str) => Ok(a_str.to_owned()),
+                                _ => Err(invalid(format!(
+                                    "options of `{}` contains non string value `{}`",
+                                    k, a
+                                ))),
+                            })
+                            .collect::<io::Result<_>>()?;
+                    };
+
+                    let kind = match get_string_option(item, "type").as_deref() {
+                        Some("bool") => ParamKind::Bool,
+                        Some("string") => ParamKind::String,
+                        Some("options") => ParamKind::Options,
+                        Some("int") => ParamKind::Int,
+                        Some("float") => ParamKind::Float,
+                        Some("list") => ParamKind::List,
+                        Some("multiselect") => ParamKind::MultiSelect,
+                        Some(t) => Err(invalid(format!("unknown type `{}` of `{}`", t, k)))?,
+                        // inferred from the default
+                        None => match item.get("default") {
+                            _ if dflt.as_deref() == Some("true")
+                                || dflt.as_deref() == Some("false") =>
+                            {
+                                ParamKind::Bool
+                            }
+                            _ if !options.is_empty() => ParamKind::Options,
+                            Some(JsonValue::Number(n)) if n.is_f64() => ParamKind::Float,
+                            Some(JsonValue::Number(_)) => ParamKind::Int,
+                            Some(JsonValue::Array(_)) => ParamKind::List,
+                            _ => ParamKind::String,
+                        },
+                    };
+                    if kind == ParamKind::MultiSelect && options.is_empty() {
+                        Err(invalid(format!("multiselect `{}` has no options", k)))?;
+                    }
+
+                    let rules: Validation = serde_json::from_value(item.clone())
+                        .map_err(|e| invalid(format!("invalid rules of `{}`: {}", k, e)))?;
+                    rules.verify(k)?;
+
+                    let mut p = Param {
+                        ask,
+                        key: k.clone(),
+                        default: dflt,
+                        value: None,
+                        ifwith: get_string_option(item, "if"),
+                        options,
+                        kind,
+                        secret: item.get("secret") == Some(&JsonValue::Bool(true)),
+                        rules,
+                    };
+                    // eg: `default = "yes"`, shown as `[Y/n]`
+                    if p.kind == ParamKind::Bool {
+                        p.default = p.default.take().map(|d| p.parse_kind(&d).unwrap_or(d));
+                    }
+
+                    params.push(p);
+                }
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// Key of param referred in expression, `param.` prefix is optional.
+fn param_key(name: &str) -> &str {
+    name.strip_prefix("param.").unwrap_or(name)
+}
+
+/// Parse the `if` condition of every param and order params by the conditions,
+/// so a param is asked after the params its condition refers to and can be evaluated
+/// against the answers given so far. Otherwise the declaration order is kept.
+fn order_by_conditions(params: Vec<Param>) -> io::Result<(Vec<Param>, HashMap<String, Expr>)> {
+    let mut conditions = HashMap::new();
+    for p in params.iter() {
+        let cond = match p.ifwith.as_ref() {
+            Some(cond) => Expr::parse(cond).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid condition of `{}`: {}", p.key, e),
+                )
+            })?,
+            None => continue,
+        };
+        if let Some(depends) = cond
+            .vars()
+            .into_iter()
+            .find(|v| !params.iter().any(|a| a.key == param_key(v)))
+        {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "condition of `{}` refers to unknown param `{}`",
+                    p.key,
+                    param_key(depends)
+                ),
+            ))?;
+        }
+        conditions.insert(p.key.clone(), cond);
+    }
+
+    // topological order, the first declared param ready is taken first
+    let depends = |p: &Param| -> Vec<&str> {
+        conditions
+            .get(&p.key)
+            .map(|cond| cond.vars().into_iter().map(param_key).collect())
+            .unwrap_or_default()
+    };
+    let mut ordered: Vec<Param> = Vec::with_capacity(params.len());
+    let mut rest = params;
+    while !rest.is_empty() {
+        let ready = rest.iter().position(|p| {
+            depends(p)
+                .iter()
+                .all(|d| ordered.iter().any(|a| a.key == *d))
+        });
+        match ready {
+            Some(i) => ordered.push(rest.remove(i)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "conditions of {} depend on each other",
+                    rest.iter()
+                        .filter(|p| conditions.contains_key(&p.key))
+                        .map(|p| format!("`{}`", p.key))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ))?,
+        }
+    }
+    Ok((ordered, conditions))
+}
+
+/// Condition of `[[present]]`, the old form `db == postgres` compares with string `postgres`
+/// unless it is a param name.
+fn present_condition(present: &Present, params: &[Param]) -> io::Result<Expr> {
+    lazy_static! {
+        static ref RE_LEGACY: Regex = Regex::new(r"^\s*(\w+)\s*==\s*([\w.-]+)\s*$").unwrap();
+    }
+    let cond = match RE_LEGACY.captures(&present.ifcond) {
+        Some(cap)
+            if !params.iter().any(|p| p.key == cap[2])
+                && !["true", "false"].contains(&&cap[2])
+                && cap[2].parse::<f64>().is_err() =>
+        {
+            format!("{} == '{}'", &cap[1], &cap[2])
+        }
+        _ => present.ifcond.to_owned(),
+    };
+    Expr::parse(&cond).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid condition of present `{}`: {}", present.path, e),
+        )
+    })
 }
 
 fn map_err<E: Display>(e: E) -> io::Error {
     io::Error::new(io::ErrorKind::InvalidInput, format!("{}", e))
 }
 
-fn read_config<P: AsRef<Path>>(path: P) -> io::Result<Config> {
+pub fn read_config<P: AsRef<Path>>(path: P) -> io::Result<Config> {
     let f = fs::read(path)?;
     let rv = String::from_utf8_lossy(f.as_slice());
-    toml::from_str(&rv).map_err(map_err)
+    let config: Config = toml::from_str(&rv).map_err(map_err)?;
+    config.project.name_rules.verify("name")?;
+    Ok(config)
 }
 
-fn get_string(o: &JsonValue, key: &'static str, field: &str) -> String {
+fn get_string(o: &JsonValue, key: &'static str, field: &str) -> io::Result<String> {
     match o.get(key) {
-        Some(JsonValue::String(a)) => a.to_owned(),
-        Some(JsonValue::Bool(a)) => format!("{}", a),
-        _ => panic!("No `{}` param for `{}`", key, field),
+        Some(JsonValue::String(a)) => Ok(a.to_owned()),
+        Some(JsonValue::Bool(a)) => Ok(format!("{}", a)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("No `{}` param for `{}`", key, field),
+        )),
     }
 }
 fn get_string_option(o: &JsonValue, key: &'static str) -> Option<String> {
     match o.get(key) {
         Some(JsonValue::String(a)) => Some(a.to_owned()),
         Some(JsonValue::Bool(a)) => Some(format!("{}", a)),
+        Some(JsonValue::Number(a)) => Some(format!("{}", a)),
+        Some(JsonValue::Array(items)) => Some(
+            items
+                .iter()
+                .map(|a| match a {
+                    JsonValue::String(a) => a.to_owned(),
+                    a => a.to_string(),
+                })
+                .join(", "),
+        ),
         _ => None,
     }
 }
 
+/// Items of list value, separated by comma.
+fn split_list(value: &str) -> Vec<&str> {
+    value
+        .split(',')
+        .map(|a| a.trim())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
 #[derive(Default)]
 struct SkipLine<'a> {
     pub matching: &'a str,
@@ -110,18 +311,25 @@ impl<'a> SkipLine<'a> {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub enum ParamKind {
     Bool,
     String,
     Options,
+    Int,
+    Float,
+    /// Values separated by comma.
+    List,
+    /// Some of the options, separated by comma.
+    MultiSelect,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Param {
     pub ask: String,
     pub key: String,
     pub default: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub value: Option<String>,
     #[serde(rename = "if")]
     pub ifwith: Option<String>,
@@ -129,6 +337,93 @@ pub struct Param {
     pub options: Vec<String>,
     // pub autogen: bool,
     pub kind: ParamKind,
+    /// Never recorded, eg: tokens.
+    #[serde(default)]
+    pub secret: bool,
+    #[serde(flatten)]
+    pub rules: Validation,
+}
+
+/// Validation rules of answer, `error` replaces the message of any broken rule.
+/// Length rules count the items of lists, `pattern` is matched by every item.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Validation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Validation {
+    pub fn is_empty(&self) -> bool {
+        self == &Validation::default()
+    }
+
+    /// Reject rules that cannot be checked, eg: an invalid `pattern` of `key`.
+    pub fn verify(&self, key: &str) -> io::Result<()> {
+        match self.pattern.as_ref().map(|pattern| Regex::new(pattern)) {
+            Some(Err(e)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid pattern of `{}`: {}", key, e),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check normalized `value` of `key`.
+    pub fn check(&self, key: &str, value: &str, kind: &ParamKind) -> io::Result<()> {
+        let items = match kind {
+            ParamKind::List | ParamKind::MultiSelect => split_list(value),
+            _ => vec![value],
+        };
+        let (len, unit) = match kind {
+            ParamKind::List | ParamKind::MultiSelect => (items.len(), "items"),
+            _ => (value.chars().count(), "characters"),
+        };
+        let number = value.parse::<f64>().ok();
+
+        let broken = if let Some(pattern) = self.pattern.as_ref().filter(|pattern| {
+            let re = Regex::new(pattern);
+            !items
+                .iter()
+                .all(|a| re.as_ref().is_ok_and(|re| re.is_match(a)))
+        }) {
+            Some(format!("expected to match `{}`", pattern))
+        } else if let Some(min) = self.min_length.filter(|min| len < *min) {
+            Some(format!("expected at least {} {}", min, unit))
+        } else if let Some(max) = self.max_length.filter(|max| len > *max) {
+            Some(format!("expected at most {} {}", max, unit))
+        } else if self.min.or(self.max).is_some() && number.is_none() {
+            Some("expected a number".to_string())
+        } else if let Some(min) = self.min.filter(|min| number < Some(*min)) {
+            Some(format!("expected at least {}", min))
+        } else {
+            self.max
+                .filter(|max| number > Some(*max))
+                .map(|max| format!("expected at most {}", max))
+        };
+
+        match broken {
+            Some(reason) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid value `{}` for `{}`, {}",
+                    value,
+                    key,
+                    self.error.as_ref().unwrap_or(&reason)
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
 }
 
 impl Param {
@@ -143,13 +438,147 @@ impl Param {
             options: vec![],
             // autogen: false,
             kind: ParamKind::String,
+            secret: false,
+            rules: Default::default(),
+        }
+    }
+
+    /// Check `value` against the kind and rules of this param, returns the normalized value.
+    pub fn parse_value(&self, value: &str) -> io::Result<String> {
+        let value = self.parse_kind(value.trim())?;
+        self.rules.check(&self.key, &value, &self.kind)?;
+        Ok(value)
+    }
+
+    fn parse_kind(&self, value: &str) -> io::Result<String> {
+        match self.kind {
+            ParamKind::Bool => match value.to_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" => Ok("true".to_string()),
+                "false" | "no" | "n" | "0" => Ok("false".to_string()),
+                _ => Err(self.invalid_value(value, "expected yes or no")),
+            },
+            ParamKind::Options if !self.options.iter().any(|a| a == value) => {
+                Err(self.invalid_value(value, &format!("only accept {}", self.options.join("/"))))
+            }
+            ParamKind::Int => value
+                .parse::<i64>()
+                .map(|n| n.to_string())
+                .map_err(|_| self.invalid_value(value, "expected an integer")),
+            ParamKind::Float => match value.parse::<f64>() {
+                Ok(n) if n.is_finite() => Ok(n.to_string()),
+                _ => Err(self.invalid_value(value, "expected a number")),
+            },
+            ParamKind::List => Ok(split_list(value).join(", ")),
+            ParamKind::MultiSelect => {
+                let items = split_list(value);
+                match items.iter().find(|a| !self.options.iter().any(|o| o == *a)) {
+                    Some(item) => Err(self
+                        .invalid_value(item, &format!("only accept {}", self.options.join("/")))),
+                    None => Ok(items.join(", ")),
+                }
+            }
+            _ => Ok(value.to_string()),
+        }
+    }
+
+    fn invalid_value(&self, value: &str, expected: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid value `{}` for `{}`, {}", value, self.key, expected),
+        )
+    }
+
+    /// Value as JSON for templates and conditions, numbers and lists of typed params are kept.
+    pub fn to_json(&self, value: &str) -> JsonValue {
+        match self.kind {
+            ParamKind::Int => value
+                .parse::<i64>()
+                .map(JsonValue::from)
+                .unwrap_or_else(|_| JsonValue::from(value)),
+            ParamKind::Float => value
+                .parse::<f64>()
+                .map(JsonValue::from)
+                .unwrap_or_else(|_| JsonValue::from(value)),
+            ParamKind::List | ParamKind::MultiSelect => JsonValue::from(split_list(value)),
+            _ if value == "true" => JsonValue::Bool(true),
+            _ if value == "false" => JsonValue::Bool(false),
+            _ => JsonValue::from(value),
+        }
+    }
+}
+
+/// Prefix of env vars presetting params, eg: `REFRAME_PARAM_AUTHOR_NAME=Robin`.
+pub const ENV_PARAM_PREFIX: &str = "REFRAME_PARAM_";
+
+/// Preset params from env vars, `REFRAME_NAME` and `REFRAME_VERSION` preset
+/// the project name and version.
+pub fn env_params<I: IntoIterator<Item = (String, String)>>(vars: I) -> Vec<Param> {
+    vars.into_iter()
+        .filter_map(|(k, v)| match k.as_str() {
+            "REFRAME_NAME" => Some(Param::new("name", v)),
+            "REFRAME_VERSION" => Some(Param::new("version", v)),
+            _ => k
+                .strip_prefix(ENV_PARAM_PREFIX)
+                .filter(|key| !key.is_empty())
+                .map(|key| Param::new(key.to_lowercase(), v)),
+        })
+        .collect()
+}
+
+/// Where the answer of a param came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnswerSource {
+    Cli,
+    AnswersFile,
+    Env,
+    Remembered,
+    UserConfig,
+    TemplateDefault,
+    Prompt,
+}
+
+impl AnswerSource {
+    pub fn describe(&self, key: &str) -> String {
+        match self {
+            AnswerSource::Cli => format!("-P:{}", key),
+            AnswerSource::AnswersFile => "answers file".to_string(),
+            AnswerSource::Env if key == "name" || key == "version" => {
+                format!("env REFRAME_{}", key.to_uppercase())
+            }
+            AnswerSource::Env => format!("env {}{}", ENV_PARAM_PREFIX, key.to_uppercase()),
+            AnswerSource::Remembered => "previous answer".to_string(),
+            AnswerSource::UserConfig => "user config".to_string(),
+            AnswerSource::TemplateDefault => "template default".to_string(),
+            AnswerSource::Prompt => "prompt".to_string(),
+        }
+    }
+}
+
+/// Answer of a param and where it came from.
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub key: String,
+    pub value: String,
+    pub kind: ParamKind,
+    pub secret: bool,
+    pub source: AnswerSource,
+}
+
+impl Answer {
+    fn new(p: &Param, source: AnswerSource) -> Self {
+        Answer {
+            key: p.key.to_owned(),
+            value: p.value.clone().unwrap_or_default(),
+            kind: p.kind.clone(),
+            secret: p.secret,
+            source,
         }
     }
 }
 
 pub struct BuiltinVar {
     pub key: &'static str,
-    pub replacer: Box<(dyn Fn(&str) -> String)>,
+    pub replacer: Box<dyn Fn(&str) -> String>,
 }
 
 #[macro_export]
@@ -165,6 +594,8 @@ macro_rules! make_case_variant {
                 options: vec![],
                 // autogen: true,
                 kind: ParamKind::String,
+                secret: $p.secret,
+                rules: Default::default(),
             });
         )*
     };
@@ -187,6 +618,7 @@ macro_rules! make_case_variants_project {
     }
 }
 
+#[allow(unused_imports)]
 pub use make_case_variant;
 
 const EXCLUDED_EXTS: &[&str] = &[
@@ -196,7 +628,7 @@ const EXCLUDED_EXTS: &[&str] = &[
 ];
 
 lazy_static! {
-    static ref RE_IF: Regex = Regex::new(r"<% if .*? %>").unwrap();
+    static ref RE_IF: Regex = Regex::new(r"<% if (.*?) %>").unwrap();
     static ref ENDIF: &'static str = "<% endif %>";
     static ref RE_SYNTAX_MARK: Regex = Regex::new(r"(#|//|/\*|--)\s*<%(.*)%>").unwrap();
     static ref RE_TEMPLATE_EXT: Regex = Regex::new(r"^(.*)\.template(.\w*)?$").unwrap();
@@ -209,6 +641,11 @@ pub struct Reframe<'a> {
     rl: &'a mut Editor<()>,
     path: PathBuf,
     dry_run: bool,
+    defaults: HashMap<String, String>,
+    remembered: HashMap<String, String>,
+    file_params: Vec<Param>,
+    env_params: Vec<Param>,
+    answers: Vec<Answer>,
 }
 
 impl<'a> Reframe<'a> {
@@ -235,15 +672,16 @@ impl<'a> Reframe<'a> {
             dirs.push(".git".to_string());
         }
 
-        let mut builtin_vars = vec![];
-        builtin_vars.push(BuiltinVar {
-            key: "year",
-            replacer: Box::new(|_| Utc::now().format("%Y").to_string()),
-        });
-        builtin_vars.push(BuiltinVar {
-            key: "month_name",
-            replacer: Box::new(|_| Utc::now().format("%B").to_string()),
-        });
+        let builtin_vars = vec![
+            BuiltinVar {
+                key: "year",
+                replacer: Box::new(|_| Utc::now().format("%Y").to_string()),
+            },
+            BuiltinVar {
+                key: "month_name",
+                replacer: Box::new(|_| Utc::now().format("%B").to_string()),
+            },
+        ];
 
         Ok(Self {
             config,
@@ -252,18 +690,99 @@ impl<'a> Reframe<'a> {
             rl,
             path: path.as_ref().to_path_buf(),
             dry_run,
+            defaults: HashMap::new(),
+            remembered: HashMap::new(),
+            file_params: vec![],
+            env_params: vec![],
+            answers: vec![],
         })
     }
 
-    fn input_read_string(&mut self, ask: String, dflt: String) -> String {
-        let rv = self.rl.readline(&ask).unwrap_or_else(|_| dflt.clone());
-        if rv.trim().is_empty() {
-            dflt
-        } else {
-            rv.trim().to_owned()
+    /// Default answers overriding the template defaults, eg: from user config.
+    pub fn set_defaults(&mut self, defaults: HashMap<String, String>) {
+        self.defaults = defaults;
+    }
+
+    /// Answers given last time, used as defaults before the user config defaults.
+    pub fn set_remembered(&mut self, remembered: HashMap<String, String>) {
+        self.remembered = remembered;
+    }
+
+    /// Params preset by answers file, `-P` params take precedence over them.
+    pub fn set_file_params(&mut self, params: Vec<Param>) {
+        self.file_params = params;
+    }
+
+    /// Params preset by env vars, `-P` params and answers file take precedence over them.
+    pub fn set_env_params(&mut self, params: Vec<Param>) {
+        self.env_params = params;
+    }
+
+    /// Source of every answer, in asking order, available after `generate`.
+    pub fn answers(&self) -> &[Answer] {
+        &self.answers
+    }
+
+    /// Value of `key` preset by `-P` or env var, with its source.
+    fn preset_value(&self, key: &str) -> Option<(String, AnswerSource)> {
+        let find = |params: &[Param]| {
+            params
+                .iter()
+                .find(|a| a.key.eq_ignore_ascii_case(key))
+                .and_then(|a| a.value.to_owned())
+        };
+        Self::param_value_opt(&self.params, key)
+            .map(|v| (v, AnswerSource::Cli))
+            .or_else(|| find(&self.file_params).map(|v| (v, AnswerSource::AnswersFile)))
+            .or_else(|| find(&self.env_params).map(|v| (v, AnswerSource::Env)))
+    }
+
+    /// Ask project name or version until it passes `rules`, the answer given last time
+    /// is the default. Without input, eg: stdin closed, the default must pass.
+    fn ask_project_field(
+        &mut self,
+        key: &str,
+        label: &str,
+        template_default: String,
+        rules: &Validation,
+    ) -> io::Result<(String, AnswerSource)> {
+        let (dflt, default_source) = match self.remembered.get(key) {
+            Some(dflt) if rules.check(key, dflt, &ParamKind::String).is_ok() => {
+                (dflt.to_owned(), AnswerSource::Remembered)
+            }
+            _ => (template_default, AnswerSource::TemplateDefault),
+        };
+        loop {
+            let question = format!("  ➢ {} ({}) : ", label.bright_blue(), dflt.yellow());
+            let (rv, closed) = match self.rl.readline(&question) {
+                Ok(rv) if !rv.trim().is_empty() => (rv.trim().to_owned(), false),
+                Ok(_) => (dflt.to_owned(), false),
+                Err(_) => (dflt.to_owned(), true),
+            };
+            match rules.check(key, &rv, &ParamKind::String) {
+                Ok(()) => {
+                    let source = if rv == dflt {
+                        default_source
+                    } else {
+                        AnswerSource::Prompt
+                    };
+                    return Ok((rv, source));
+                }
+                Err(e) if closed => Err(e)?,
+                Err(e) => println!("    {}", e),
+            }
         }
     }
 
+    /// Value of param `k` for expressions, `param.` prefix is optional.
+    fn lookup_value(params: &[Param], k: &str) -> Option<JsonValue> {
+        let k = param_key(k);
+        params
+            .iter()
+            .find(|a| a.key == k)
+            .and_then(|a| a.value.as_ref().map(|v| a.to_json(v)))
+    }
+
     fn param_value_opt(params: &[Param], k: &str) -> Option<String> {
         params
             .iter()
@@ -272,12 +791,84 @@ impl<'a> Reframe<'a> {
             .map(|a| a.to_owned())
     }
 
-    fn param_value(params: &[Param], k: &str) -> String {
-        Self::param_value_opt(params, k).unwrap_or_else(|| "".to_string())
-    }
+    /// Ask the value of `p` until a valid answer given, quiet mode takes the default.
+    fn ask_param(
+        &mut self,
+        p: &mut Param,
+        quiet_mode: bool,
+        default_source: AnswerSource,
+    ) -> io::Result<()> {
+        let hint = match p.kind {
+            ParamKind::Options => format!(" [{}]", p.options.join("/")),
+            ParamKind::MultiSelect => format!(" [{}, separated by comma]", p.options.join("/")),
+            ParamKind::List => " [separated by comma]".to_string(),
+            // confirm prompt, the capital one is the default
+            ParamKind::Bool => match p.default.as_deref() {
+                Some("true") => " [Y/n]".to_string(),
+                Some("false") => " [y/N]".to_string(),
+                _ => " [y/n]".to_string(),
+            },
+            _ => "".to_string(),
+        };
+        loop {
+            let question = if p.kind == ParamKind::Bool {
+                format!("  ➢ {}{} : ", p.ask.bright_blue(), hint)
+            } else if let Some(dflt) = p.default.as_ref() {
+                format!("  ➢ {}{} ({}) : ", p.ask.bright_blue(), hint, dflt.yellow())
+            } else {
+                format!("  ➢ {}{} : ", p.ask.bright_blue(), hint)
+            };
 
-    fn get_value_from_param(&self, key: &str) -> Option<String> {
-        Self::param_value_opt(&self.params, key)
+            let rv = if !quiet_mode {
+                self.rl.readline(&question).map_err(map_err)?
+            } else {
+                p.default.clone().unwrap_or("".to_string())
+            };
+
+            let (rv, source) = if !rv.trim().is_empty() {
+                let source = if quiet_mode {
+                    default_source
+                } else {
+                    AnswerSource::Prompt
+                };
+                (rv, source)
+            } else if let Some(dflt) = p.default.clone() {
+                (dflt, default_source)
+            } else if quiet_mode {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "param required: `{}`, preset it with -P:{}=... or {}{}",
+                        p.key,
+                        p.key,
+                        ENV_PARAM_PREFIX,
+                        p.key.to_uppercase()
+                    ),
+                ))?
+            } else {
+                println!("    Param required: `{}`", &p.key);
+                continue;
+            };
+
+            // the default is checked too, template may declare a default breaking its rules
+            let rv = match p.parse_value(&rv) {
+                Ok(rv) => rv,
+                Err(e) if quiet_mode => Err(e)?,
+                Err(e) => {
+                    println!("    {}", e);
+                    continue;
+                }
+            };
+
+            if !p.secret {
+                self.rl.add_history_entry(rv.clone());
+            }
+
+            p.value = Some(rv);
+            self.answers.push(Answer::new(p, source));
+
+            return Ok(());
+        }
     }
 
     #[allow(clippy::option_map_unit_fn)]
@@ -292,20 +883,39 @@ impl<'a> Reframe<'a> {
         } else {
             out_dir.as_ref().to_path_buf()
         };
-        let project_name = self.get_value_from_param("name").unwrap_or_else(|| {
-            self.input_read_string(
-                format!(
-                    "  ➢ {} ({}) : ",
-                    "Project name".bright_blue(),
-                    &self.config.project.name.yellow()
-                ),
-                self.config.project.name.to_owned(),
-            )
-        });
-
-        if project_name != "" {
+        self.answers.clear();
+        let (mut params, conditions) = order_by_conditions(self.config.params()?)?;
+        // fail before asking anything or writing the output
+        self.check_conditionals(self.path.as_path())?;
+
+        let rules = self.config.project.name_rules.clone();
+        let (project_name, source) = match self.preset_value("name") {
+            Some((name, source)) => {
+                rules
+                    .check("name", &name, &ParamKind::String)
+                    .map_err(|e| {
+                        io::Error::new(
+                            e.kind(),
+                            format!("{} (from {})", e, source.describe("name")),
+                        )
+                    })?;
+                (name, source)
+            }
+            None => {
+                let dflt = self.config.project.name.to_owned();
+                self.ask_project_field("name", "Project name", dflt, &rules)?
+            }
+        };
+        if !project_name.is_empty() {
             self.config.project.name = project_name;
         }
+        self.answers.push(Answer {
+            key: "name".to_string(),
+            value: self.config.project.name.to_owned(),
+            kind: ParamKind::String,
+            secret: false,
+            source,
+        });
 
         make_case_variants_project!(
             self,
@@ -321,60 +931,66 @@ impl<'a> Reframe<'a> {
             ]
         );
 
-        let version = self.get_value_from_param("version").unwrap_or_else(|| {
-            self.rl
-                .readline(&format!(
-                    "  ➢ {} ({}) : ",
-                    "Version".bright_blue(),
-                    &self.config.project.version.yellow()
-                ))
-                .unwrap_or_else(|_| self.config.project.version.to_owned())
-        });
-
-        if version != "" {
+        let (version, source) = match self.preset_value("version") {
+            Some(preset) => preset,
+            None => {
+                let dflt = self.config.project.version.to_owned();
+                self.ask_project_field("version", "Version", dflt, &Validation::default())?
+            }
+        };
+        if !version.is_empty() {
             self.config.project.version = version;
         }
+        self.answers.push(Answer {
+            key: "version".to_string(),
+            value: self.config.project.version.to_owned(),
+            kind: ParamKind::String,
+            secret: false,
+            source,
+        });
 
-        for item in &self.config.param {
-            if let JsonValue::Object(o) = &item {
-                for (k, item) in o {
-                    let ask = get_string(item, "ask", k);
-                    let dflt = get_string_option(item, "default");
-                    let mut options: Vec<String> = vec![];
-                    if let Some(JsonValue::Array(values)) = item.get("options") {
-                        options = values
-                            .iter()
-                            .map(|a| match a {
-                                JsonValue::String(a_str) => a_str.to_owned(),
-                                _ => panic!("options contains non string value `{}`", &a),
-                            })
-                            .collect();
-                    };
-
-                    let kind = if dflt.as_ref().map(|a| a.as_str()) == Some("true")
-                        || dflt.as_ref().map(|a| a.as_str()) == Some("false")
-                    {
-                        ParamKind::Bool
-                    } else if !options.is_empty() {
-                        ParamKind::Options
-                    } else {
-                        ParamKind::String
-                    };
-
-                    let p = Param {
-                        ask,
-                        key: k.clone(),
-                        default: dflt,
-                        value: None,
-                        ifwith: get_string_option(item, "if"),
-                        options,
-                        kind,
-                    };
+        let mut default_sources = HashMap::new();
+        for p in params.iter_mut() {
+            for (defaults, source) in [
+                (&self.remembered, AnswerSource::Remembered),
+                (&self.defaults, AnswerSource::UserConfig),
+            ] {
+                if let Some(dflt) = defaults.get(&p.key) {
+                    match p.parse_value(dflt) {
+                        Ok(dflt) => {
+                            p.default = Some(dflt);
+                            default_sources.insert(p.key.clone(), source);
+                            break;
+                        }
+                        Err(e) => debug!("ignoring default: {}", e),
+                    }
+                }
+            }
+        }
 
-                    self.params.push(p);
+        // params not asked by the template are passed through as is
+        let is_extra = |p: &Param| {
+            !["name", "version"].contains(&p.key.as_str())
+                && !params.iter().any(|a| a.key.eq_ignore_ascii_case(&p.key))
+        };
+        let mut extras: Vec<Answer> = self
+            .params
+            .iter()
+            .filter(|p| is_extra(p))
+            .map(|p| Answer::new(p, AnswerSource::Cli))
+            .collect();
+        for (presets, source) in [
+            (&self.file_params, AnswerSource::AnswersFile),
+            (&self.env_params, AnswerSource::Env),
+        ] {
+            for ep in presets.iter() {
+                if is_extra(ep) && !self.params.iter().any(|a| a.key == ep.key) {
+                    self.params.push(ep.clone());
+                    extras.push(Answer::new(ep, source));
                 }
             }
         }
+        self.params.extend(params);
 
         let (pre_params, mut new_params): (Vec<Param>, Vec<Param>) =
             self.params.iter().partition_map(|p| match p.ask.len() {
@@ -385,62 +1001,31 @@ impl<'a> Reframe<'a> {
         let mut final_params = pre_params.clone();
 
         for p in new_params.iter_mut() {
-            if let Some(depends) = p.ifwith.as_ref() {
-                if Self::param_value(&self.params, depends) == "false" {
+            // evaluated against the answers so far, skipped params are null
+            if let Some(cond) = conditions.get(&p.key) {
+                if !cond.is_true(&|k: &str| Self::lookup_value(&final_params, k)) {
                     continue;
                 }
             }
 
-            if pre_params.iter().find(|a| a.key == p.key).is_some() {
-                continue;
-            }
-
-            loop {
-                let question = if let Some(dflt) = p.default.as_ref() {
-                    if !p.options.is_empty() {
-                        format!(
-                            "  ➢ {} [{}] ({}) : ",
-                            p.ask.bright_blue(),
-                            p.options.join("/"),
-                            dflt.yellow()
-                        )
-                    } else {
-                        format!("  ➢ {} ({}) : ", p.ask.bright_blue(), dflt.yellow())
-                    }
-                } else {
-                    format!("  ➢ {} : ", p.ask.bright_blue())
-                };
-
-                let mut rv = if !quiet_mode {
-                    self.rl.readline(&question).map_err(map_err)?
-                } else {
-                    p.default.clone().unwrap_or("".to_string())
-                };
-
-                rv = rv.trim().to_string();
-
-                if !rv.is_empty() {
-                    if !p.options.is_empty() && !p.options.contains(&rv) {
-                        println!(
-                            "    Value not supported `{}`, only accept {}",
-                            rv,
-                            p.options.join("/")
-                        );
-                        continue;
-                    }
-                } else if p.default.as_ref().is_some() {
-                    rv = p.default.as_ref().unwrap().to_owned();
-                } else {
-                    println!("    Param required: `{}`", &p.key);
-                    continue;
-                }
-
-                self.rl.add_history_entry(rv.clone());
-
-                p.value = Some(rv);
+            if let Some((value, source)) = self.preset_value(&p.key) {
+                let value = p.parse_value(&value).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("{} (from {})", e, source.describe(&p.key)),
+                    )
+                })?;
+                final_params.retain(|a| a.key != p.key);
+                p.value = Some(value);
+                final_params.push(p.clone());
+                self.answers.push(Answer::new(p, source));
+            } else {
+                let default_source = default_sources
+                    .get(&p.key)
+                    .copied()
+                    .unwrap_or(AnswerSource::TemplateDefault);
+                self.ask_param(p, quiet_mode, default_source)?;
                 final_params.push(p.clone());
-
-                break;
             }
 
             // buat variasi case-nya
@@ -467,14 +1052,14 @@ impl<'a> Reframe<'a> {
         }
 
         self.params = final_params;
+        self.answers.extend(extras);
 
         let out_dir = out_name
             .as_ref()
             .map(|a| out_dir.join(a))
             .unwrap_or_else(|| {
                 out_dir.join(
-                    &self
-                        .config
+                    self.config
                         .project
                         .variants
                         .get("name_kebab_case")
@@ -532,12 +1117,10 @@ impl<'a> Reframe<'a> {
             if let Some(path) = pg_op.make_executable.as_ref() {
                 let path = Self::string_sub(path, &self.config, &self.params, &self.builtin_vars);
                 let path = out_dir.join(path);
-                if Path::new(&path).is_file() {
-                    if cfg!(unix) {
-                        debug!("chmod'ing {}...", path.display());
-                        if let Err(e) = Command::new("chmod").arg("+x").arg(&path).output() {
-                            error!("Cannot chmod +x `{}`. {}", path.display(), e);
-                        }
+                if Path::new(&path).is_file() && cfg!(unix) {
+                    debug!("chmod'ing {}...", path.display());
+                    if let Err(e) = Command::new("chmod").arg("+x").arg(&path).output() {
+                        error!("Cannot chmod +x `{}`. {}", path.display(), e);
                     }
                 }
             }
@@ -589,7 +1172,7 @@ impl<'a> Reframe<'a> {
                 .project
                 .ignore_files
                 .as_ref()
-                .map(|patts| util::file_pattern_match(&tail_name, &patts[..]))
+                .map(|patts| util::file_pattern_match(tail_name, &patts[..]))
                 == Some(true)
             {
                 debug!("`{}` ignored", &tail_name.to_string());
@@ -599,31 +1182,8 @@ impl<'a> Reframe<'a> {
             // check presents
             for present in &self.config.presents {
                 if util::path_to_relative(&path, &self.path).as_path() == Path::new(&present.path) {
-                    let mut no_match = 0;
-                    for param in &self.params {
-                        if param.key == present.ifcond {
-                            if param.kind == ParamKind::Bool {
-                                if let Some("false") = param.value.as_ref().map(|a| a.as_ref()) {
-                                    continue 'dirwalk;
-                                }
-                            }
-                        } else if present.ifcond.contains('=')
-                            && present.ifcond.starts_with(&param.key)
-                        {
-                            if let Some(value) = param.value.as_ref() {
-                                if format!("{} == {}", param.key, value) != present.ifcond {
-                                    continue 'dirwalk;
-                                }
-                            } else {
-                                // tidak terdefinisikan.
-                                continue 'dirwalk;
-                            }
-                        } else {
-                            no_match += 1;
-                        }
-                    }
-                    if no_match == self.params.len() {
-                        // param tidak terdefinisikan.
+                    let cond = present_condition(present, &self.params)?;
+                    if !cond.is_true(&|k: &str| Self::lookup_value(&self.params, k)) {
                         continue 'dirwalk;
                     }
                 }
@@ -646,16 +1206,79 @@ impl<'a> Reframe<'a> {
         Ok(())
     }
 
+    /// Parse every `<% if %>` conditional of the template files and check it is closed,
+    /// so a broken one is reported with its file and line up front.
+    fn check_conditionals(&self, dir: &Path) -> io::Result<()> {
+        for item in fs::read_dir(dir)? {
+            let path = item?.path();
+            let tail_name = path.file_name().unwrap().to_string_lossy().to_string();
+            let project = &self.config.project;
+            if project
+                .ignore_dirs
+                .as_ref()
+                .map(|dirs| dirs.contains(&tail_name))
+                == Some(true)
+                || project
+                    .ignore_files
+                    .as_ref()
+                    .map(|patts| util::file_pattern_match(&tail_name, &patts[..]))
+                    == Some(true)
+            {
+                continue;
+            }
+
+            if path.is_dir() {
+                self.check_conditionals(&path)?;
+                continue;
+            }
+            if let Some(ext) = path.extension() {
+                if EXCLUDED_EXTS.contains(&ext.to_string_lossy().as_ref()) {
+                    continue;
+                }
+            }
+
+            let text = String::from_utf8_lossy(&fs::read(&path)?).to_string();
+            let file = util::path_to_relative(&path, &self.path);
+            let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+            let mut open = vec![];
+            for (i, line) in text.split('\n').enumerate() {
+                if let Some(cap) = RE_IF.captures(line) {
+                    Expr::parse(&cap[1]).map_err(|e| {
+                        invalid(format!(
+                            "invalid if conditional `{}` in `{}` at line {}: {}",
+                            line.trim(),
+                            file.display(),
+                            i + 1,
+                            e
+                        ))
+                    })?;
+                    open.push((i, line));
+                } else if line.contains(*ENDIF) {
+                    open.pop();
+                }
+            }
+            if let Some((i, line)) = open.pop() {
+                Err(invalid(format!(
+                    "unclosed if conditional `{}` in `{}` at line {}",
+                    line.trim(),
+                    file.display(),
+                    i + 1
+                )))?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn process_template_str(
         text: String,
         config: &Config,
         params: &[Param],
         builtin_vars: &[BuiltinVar],
-    ) -> String {
+    ) -> io::Result<String> {
         let lines: Vec<&str> = text.split('\n').collect();
         let mut new_lines = vec![];
         let mut sl = SkipLine::new();
-        let len = lines.len();
         let mut last_if_cond: &str = "";
         let mut last_if_cond_line: usize = 0;
 
@@ -663,78 +1286,55 @@ impl<'a> Reframe<'a> {
 
         for (i, line) in lines.iter().enumerate() {
             if sl.start {
-                if line.contains(&sl.matching) {
+                if line.contains(sl.matching) {
                     sl.stop();
                 }
-                if i >= len - 1 {
-                    panic!(
-                        "unclosed if conditional `{}` at line {}",
-                        last_if_cond.trim(),
-                        last_if_cond_line
-                    );
-                }
                 continue;
             }
 
-            if RE_IF.is_match(&line) {
+            if let Some(cap) = RE_IF.captures(line) {
                 last_if_cond = line;
-                last_if_cond_line = i;
-                let mut if_handled = false;
-                for p in params.iter() {
-                    let k = &p.key;
-                    let v = match p.value.as_ref() {
-                        Some(v) => v,
-                        None => {
-                            debug!("no value with key: {}", k);
-                            continue;
-                        }
-                    };
-
-                    if k.starts_with("with_") {
-                        if v == "false" {
-                            if line.contains(&format!("<% if param.{} %>", k)) {
-                                debug!("skip...");
-                                sl.start = true;
-                                sl.matching = "<% endif %>";
-                                break;
-                            }
-                        } else {
-                            if_handled = true;
-                        }
-                    } else {
-                        let re_txt = format!(r#"(//|#|--)\s*<% if param.{}\s*==\s*"(.*)" %>"#, k);
-                        let re_if_compare = Regex::new(&re_txt).unwrap();
-                        let mut caps = re_if_compare.captures_iter(&line);
-                        if let Some(cap) = caps.next() {
-                            if &cap[2] != v.as_str() {
-                                sl.start = true;
-                                sl.matching = "<% endif %>";
-                            } else {
-                                new_lines.push(line.to_owned());
-                            }
-                            if_handled = true;
-                        }
-                    }
-                }
-                // apabila tidak ada param yang menghandle
-                // swallow aja semua block-nya.
-                if !if_handled {
+                last_if_cond_line = i + 1;
+                let cond = Expr::parse(&cap[1]).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "invalid if conditional `{}` at line {}: {}",
+                            line.trim(),
+                            i + 1,
+                            e
+                        ),
+                    )
+                })?;
+                if cond.is_true(&|k: &str| Self::lookup_value(params, k)) {
+                    new_lines.push(line.to_owned());
+                } else {
+                    // param tidak terdefinisikan atau false,
+                    // swallow aja semua block-nya.
                     sl.start = true;
                     sl.matching = "<% endif %>";
-                } else {
-                    new_lines.push(line.to_owned());
                 }
             } else if !line.contains(*ENDIF) {
                 new_lines.push(line.to_owned());
             }
         }
+        if sl.start {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "unclosed if conditional `{}` at line {}",
+                    last_if_cond.trim(),
+                    last_if_cond_line
+                ),
+            ))?;
+        }
 
         // proses tahap #2
 
         let mut new_lines2 = vec![];
 
         for line in new_lines {
-            if RE_SYNTAX_MARK.is_match(&line) {
+            if RE_SYNTAX_MARK.is_match(line) {
                 continue;
             }
             new_lines2.push(Self::string_sub(
@@ -745,17 +1345,7 @@ impl<'a> Reframe<'a> {
             ));
         }
 
-        new_lines2.join("\n")
-    }
-
-    fn to_json_value(value: &str) -> serde_json::Value {
-        if value == "true" {
-            serde_json::Value::Bool(true)
-        } else if value == "false" {
-            serde_json::Value::Bool(false)
-        } else {
-            serde_json::Value::String(value.to_owned())
-        }
+        Ok(new_lines2.join("\n"))
     }
 
     pub fn process_with_handlebars(
@@ -776,10 +1366,10 @@ impl<'a> Reframe<'a> {
 
         for param in params {
             if let Some(value) = &param.value {
-                data.insert(&param.key, Self::to_json_value(value));
+                data.insert(&param.key, param.to_json(value));
             } else {
                 if let Some(dflt) = &param.default {
-                    data.insert(&param.key, Self::to_json_value(dflt));
+                    data.insert(&param.key, param.to_json(dflt));
                 }
             }
         }
@@ -801,7 +1391,10 @@ impl<'a> Reframe<'a> {
         )
         .to_string();
 
-        let rv = Self::process_template_str(rv, &self.config, &self.params, &self.builtin_vars);
+        let rv = Self::process_template_str(rv, &self.config, &self.params, &self.builtin_vars)
+            .map_err(|e| {
+                io::Error::new(e.kind(), format!("{} of `{}`", e, path.as_ref().display()))
+            })?;
 
         let out_path = format!("{}", path.as_ref().display());
 
@@ -846,7 +1439,7 @@ impl<'a> Reframe<'a> {
         }
 
         for v in builtin_vars.iter() {
-            rep = rep.replace(&format!("${}$", v.key), &(&v.replacer)(&rep));
+            rep = rep.replace(&format!("${}$", v.key), &(v.replacer)(&rep));
         }
 
         rep = rep.replace("$version$", &config.project.version);
@@ -993,6 +1586,7 @@ mod tests {
                 ignore_dirs: None,
                 ignore_files: None,
                 finish_text: None,
+                name_rules: Default::default(),
             },
             param: vec![],
             presents: vec![],
@@ -1019,4 +1613,510 @@ mod tests {
         let output = Reframe::string_sub(input, &config, &param, &[]);
         assert_eq!(output, expected);
     }
+
+    /// Template dir with `Reframe.toml` declaring `params` and a README using them.
+    pub(crate) fn make_template(name: &str, params: &str, readme: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "reframe_test_{}_{}",
+            name,
+            util::get_current_time_millis()
+        ));
+        let tpl = dir.join("template");
+        fs::create_dir_all(&tpl).unwrap();
+        fs::write(
+            tpl.join("Reframe.toml"),
+            format!(
+                "[reframe]\nname = \"Test\"\nauthor = \"test\"\nmin_version = \"0.1.0\"\n\n\
+                 [project]\nname = \"Hello\"\nversion = \"0.1.0\"\n\n{}",
+                params
+            ),
+        )
+        .unwrap();
+        fs::write(tpl.join("README.md"), readme).unwrap();
+        dir
+    }
+
+    /// Generate `template` in quiet mode, returns the generated README.
+    pub(crate) fn generate_quiet(
+        dir: &Path,
+        params: Vec<Param>,
+        defaults: HashMap<String, String>,
+        remembered: HashMap<String, String>,
+    ) -> io::Result<String> {
+        let mut rl = Editor::<()>::new().unwrap();
+        let mut params = params;
+        params.push(Param::new("name", "Hello"));
+        params.push(Param::new("version", "0.1.0"));
+        let mut rf = Reframe::open(dir.join("template"), &mut rl, false, params)?;
+        rf.set_defaults(defaults);
+        rf.set_remembered(remembered);
+        let out = rf.generate(dir, Some("out"), true)?.unwrap();
+        fs::read_to_string(Path::new(&out).join("README.md"))
+    }
+
+    #[test]
+    fn test_user_defaults() {
+        let dir = make_template(
+            "user_defaults",
+            "[[param]]\nauthor_name = { ask = \"Author?\", default = \"nobody\" }\n\n\
+             [[param]]\nlicense = { ask = \"License?\", default = \"MIT\", options = [\"MIT\", \"BSD\"] }\n\n\
+             [[param]]\nci = { ask = \"CI?\", default = \"github\" }\n",
+            "$param.author_name$ $param.license$ $param.ci$",
+        );
+        let defaults: HashMap<String, String> = [
+            ("author_name", "Robin"),
+            ("license", "GPL"), // not in options, ignored
+            ("ci", "gitlab"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        // user config defaults override template defaults, -P overrides both
+        let readme = generate_quiet(
+            &dir,
+            vec![Param::new("ci", "none")],
+            defaults.to_owned(),
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(readme.trim_end(), "Robin MIT none");
+
+        // previous answers override user config defaults
+        let remembered: HashMap<String, String> = [("author_name", "Anvie"), ("license", "BSD")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let readme = generate_quiet(&dir, vec![], defaults, remembered).unwrap();
+        assert_eq!(readme.trim_end(), "Anvie BSD gitlab");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_env_params() {
+        let dir = make_template(
+            "env_params",
+            "[[param]]\nauthor_name = { ask = \"Author?\", default = \"nobody\" }\n\n\
+             [[param]]\nwith_ci = { ask = \"CI?\", default = true }\n\n\
+             [[param]]\nci = { ask = \"Which CI?\", default = \"github\", if = \"with_ci\" }\n",
+            "$param.author_name$ $param.author_name_shout_snake_case$ $param.with_ci$ $param.ci$ $param.extra$",
+        );
+        let vars = [
+            ("REFRAME_PARAM_AUTHOR_NAME", "Robin Syihab"),
+            ("REFRAME_PARAM_WITH_CI", "no"),
+            ("REFRAME_PARAM_EXTRA", "x"),
+            ("REFRAME_VERSION", "2.0"),
+            ("HOME", "/root"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let params = env_params(vars);
+        assert_eq!(params.len(), 4);
+        assert_eq!(params[0].key, "author_name");
+        assert_eq!(params[3].key, "version");
+
+        let mut rl = Editor::<()>::new().unwrap();
+        let mut rf = Reframe::open(
+            dir.join("template"),
+            &mut rl,
+            false,
+            vec![Param::new("name", "Hello"), Param::new("version", "1.0")],
+        )
+        .unwrap();
+        rf.set_env_params(params.to_owned());
+        rf.set_file_params(vec![Param::new("author_name", "Anvie")]);
+        let out = rf.generate(&dir, Some("out"), true).unwrap().unwrap();
+        let readme = fs::read_to_string(Path::new(&out).join("README.md")).unwrap();
+        assert_eq!(readme.trim_end(), "Anvie ANVIE false $param.ci$ x");
+
+        let sources: Vec<(&str, &str, AnswerSource)> = rf
+            .answers()
+            .iter()
+            .map(|a| (a.key.as_str(), a.value.as_str(), a.source))
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                ("name", "Hello", AnswerSource::Cli),
+                ("version", "1.0", AnswerSource::Cli),
+                ("author_name", "Anvie", AnswerSource::AnswersFile),
+                ("with_ci", "false", AnswerSource::Env),
+                ("extra", "x", AnswerSource::Env),
+            ]
+        );
+        assert_eq!(
+            AnswerSource::Env.describe("author_name"),
+            "env REFRAME_PARAM_AUTHOR_NAME"
+        );
+
+        // value not matching the param kind
+        let mut rl = Editor::<()>::new().unwrap();
+        let mut rf = Reframe::open(
+            dir.join("template"),
+            &mut rl,
+            false,
+            vec![Param::new("name", "Hello"), Param::new("version", "1.0")],
+        )
+        .unwrap();
+        rf.set_env_params(env_params(vec![(
+            "REFRAME_PARAM_WITH_CI".to_string(),
+            "maybe".to_string(),
+        )]));
+        let err = rf.generate(&dir, Some("out"), true).unwrap_err();
+        assert!(err.to_string().contains("from env REFRAME_PARAM_WITH_CI"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_conditional_params() {
+        let dir = make_template(
+            "conditional_params",
+            "[[param]]\nwith_serde = { ask = \"Serde?\", default = false }\n\n\
+             [[param]]\nserde_version = { ask = \"Serde version?\", default = \"1.0\", if = \"with_serde\" }\n\n\
+             [[param]]\nserde_json = { ask = \"Serde json?\", default = true, if = \"serde_version\" }\n",
+            "$param.with_serde$ $param.serde_version$ $param.serde_json$",
+        );
+
+        // skipped params don't satisfy conditions depending on them
+        let readme = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(
+            readme.trim_end(),
+            "false $param.serde_version$ $param.serde_json$"
+        );
+
+        // condition sees the answer given in the same session, not only presets
+        let remembered: HashMap<String, String> = [("with_serde".to_string(), "yes".to_string())]
+            .into_iter()
+            .collect();
+        let readme = generate_quiet(&dir, vec![], HashMap::new(), remembered).unwrap();
+        assert_eq!(readme.trim_end(), "true 1.0 true");
+
+        let readme = generate_quiet(
+            &dir,
+            vec![
+                Param::new("with_serde", "y"),
+                Param::new("serde_json", "no"),
+            ],
+            HashMap::new(),
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(readme.trim_end(), "true 1.0 false");
+        let _ = fs::remove_dir_all(&dir);
+
+        // dependent params declared before their dependency are asked after it,
+        // `param.` prefix refers to the same param
+        let dir = make_template(
+            "conditional_params_order",
+            "[[param]]\nserde_json = { ask = \"Serde json?\", default = true, if = \"param.serde_version\" }\n\n\
+             [[param]]\nserde_version = { ask = \"Serde version?\", default = \"1.0\", if = \"with_serde\" }\n\n\
+             [[param]]\nwith_serde = { ask = \"Serde?\", default = false }\n",
+            "$param.with_serde$ $param.serde_version$ $param.serde_json$",
+        );
+        let readme = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(
+            readme.trim_end(),
+            "false $param.serde_version$ $param.serde_json$"
+        );
+        let mut rl = Editor::<()>::new().unwrap();
+        let mut rf = Reframe::open(
+            dir.join("template"),
+            &mut rl,
+            false,
+            vec![
+                Param::new("name", "Hello"),
+                Param::new("version", "1.0"),
+                Param::new("with_serde", "yes"),
+            ],
+        )
+        .unwrap();
+        let out = rf.generate(&dir, Some("out"), true).unwrap().unwrap();
+        let readme = fs::read_to_string(Path::new(&out).join("README.md")).unwrap();
+        assert_eq!(readme.trim_end(), "true 1.0 true");
+        let asked: Vec<&str> = rf.answers().iter().map(|a| a.key.as_str()).collect();
+        assert_eq!(
+            asked,
+            vec![
+                "name",
+                "version",
+                "with_serde",
+                "serde_version",
+                "serde_json"
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+
+        for (params, expected) in [
+            (
+                "[[param]]\nserde_version = { ask = \"Version?\", if = \"with_serde\" }\n\n\
+                 [[param]]\nwith_serde = { ask = \"Serde?\", default = false, if = \"serde_version\" }\n",
+                "conditions of `serde_version`, `with_serde` depend on each other",
+            ),
+            (
+                "[[param]]\nserde_version = { ask = \"Version?\", if = \"param.with_sered\" }\n",
+                "condition of `serde_version` refers to unknown param `with_sered`",
+            ),
+        ] {
+            let dir = make_template("conditional_params_invalid", params, "");
+            let err = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn test_condition_expressions() {
+        let dir = make_template(
+            "condition_expressions",
+            "[[param]]\ndb = { ask = \"Database?\", default = \"postgres\", options = [\"postgres\", \"mysql\", \"sqlite\"] }\n\n\
+             [[param]]\nwith_docker = { ask = \"Docker?\", default = false }\n\n\
+             [[param]]\npool_size = { ask = \"Pool size?\", default = \"10\", if = \"db in ['postgres', 'mysql'] && !with_docker\" }\n\n\
+             [[present]]\npath = \"pg.sql\"\nif = \"db == postgres\"\n\n\
+             [[present]]\npath = \"Dockerfile\"\nif = \"with_docker || len(pool_size) > 2\"\n",
+            "# <% if param.db != 'sqlite' && pool_size >= 10 %>\npool\n# <% endif %>\n$param.pool_size$",
+        );
+        let tpl = dir.join("template");
+        fs::write(tpl.join("pg.sql"), "").unwrap();
+        fs::write(tpl.join("Dockerfile"), "").unwrap();
+        let out = dir.join("out");
+
+        let readme = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(readme.trim_end(), "pool\n10");
+        assert!(out.join("pg.sql").exists());
+        assert!(!out.join("Dockerfile").exists());
+
+        let params = vec![Param::new("db", "mysql"), Param::new("with_docker", "yes")];
+        let readme = generate_quiet(&dir, params, HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(readme.trim_end(), "$param.pool_size$");
+        assert!(!out.join("pg.sql").exists());
+        assert!(out.join("Dockerfile").exists());
+
+        let params = vec![Param::new("db", "mysql"), Param::new("pool_size", "5")];
+        let readme = generate_quiet(&dir, params, HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(readme.trim_end(), "5");
+
+        fs::create_dir_all(tpl.join("src")).unwrap();
+        for (main_rs, expected) in [
+            (
+                "fn main() {\n// <% if db == %>\n// <% endif %>\n}\n",
+                "invalid if conditional `// <% if db == %>` in `src/main.rs` at line 2: ",
+            ),
+            (
+                "fn main() {\n// <% if with_docker %>\n}\n",
+                "unclosed if conditional `// <% if with_docker %>` in `src/main.rs` at line 2",
+            ),
+        ] {
+            fs::write(tpl.join("src/main.rs"), main_rs).unwrap();
+            let _ = fs::remove_dir_all(&out);
+            let err = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap_err();
+            assert!(err.to_string().starts_with(expected), "{}", err);
+            assert!(!out.exists());
+        }
+        fs::remove_dir_all(tpl.join("src")).unwrap();
+
+        fs::write(
+            tpl.join("Reframe.toml"),
+            fs::read_to_string(tpl.join("Reframe.toml"))
+                .unwrap()
+                .replace("!with_docker", "!with_docker ||"),
+        )
+        .unwrap();
+        let err = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid condition of `pool_size`: "));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_typed_params() {
+        let dir = make_template(
+            "typed_params",
+            "[[param]]\nworkers = { ask = \"Workers?\", default = 4 }\n\n\
+             [[param]]\nratio = { ask = \"Ratio?\", default = \"0.5\", type = \"float\" }\n\n\
+             [[param]]\nfeatures = { ask = \"Features?\", default = [\"auth\", \"admin\"] }\n\n\
+             [[param]]\ntargets = { ask = \"Targets?\", type = \"multiselect\", options = [\"linux\", \"macos\", \"windows\"], default = \"linux\" }\n\n\
+             [[param]]\nbig = { ask = \"Big?\", default = true, if = \"workers > 8 || len(features) > 2\" }\n",
+            "{{#if (gt workers 2)}}many{{/if}} {{ratio}} {{#each features}}[{{this}}]{{/each}} \
+             {{targets.[1]}} $param.features$ $param.big$",
+        );
+        let readme = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(
+            readme.trim_end(),
+            "many 0.5 [auth][admin]  auth, admin $param.big$"
+        );
+
+        let params = vec![
+            Param::new("workers", "16"),
+            Param::new("features", "auth,,db ,"),
+            Param::new("targets", "linux,windows"),
+        ];
+        let readme = generate_quiet(&dir, params, HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(
+            readme.trim_end(),
+            "many 0.5 [auth][db] windows auth, db true"
+        );
+
+        for (key, value, expected) in [
+            (
+                "workers",
+                "4.5",
+                "invalid value `4.5` for `workers`, expected an integer",
+            ),
+            (
+                "ratio",
+                "half",
+                "invalid value `half` for `ratio`, expected a number",
+            ),
+            (
+                "targets",
+                "linux, bsd",
+                "invalid value `bsd` for `targets`, only accept linux/macos/windows",
+            ),
+        ] {
+            let err = generate_quiet(
+                &dir,
+                vec![Param::new(key, value)],
+                HashMap::new(),
+                HashMap::new(),
+            )
+            .unwrap_err();
+            assert!(err.to_string().starts_with(expected), "{}", err);
+        }
+
+        let tpl = dir.join("template");
+        let config = fs::read_to_string(tpl.join("Reframe.toml")).unwrap();
+        for (from, to, expected) in [
+            (
+                "type = \"float\"",
+                "type = \"decimal\"",
+                "unknown type `decimal` of `ratio`",
+            ),
+            (
+                "options = [\"linux\", \"macos\", \"windows\"], ",
+                "",
+                "multiselect `targets` has no options",
+            ),
+        ] {
+            fs::write(tpl.join("Reframe.toml"), config.replace(from, to)).unwrap();
+            let err = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), expected);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_validation_rules() {
+        let dir = make_template(
+            "validation_rules",
+            "[[param]]\ncrate_name = { ask = \"Crate?\", default = \"hello\", pattern = \"^[a-z][a-z0-9_]*$\", \
+             error = \"must be lowercase snake_case\" }\n\n\
+             [[param]]\nworkers = { ask = \"Workers?\", default = 4, min = 1, max = 64 }\n\n\
+             [[param]]\ntags = { ask = \"Tags?\", type = \"list\", default = \"\", max_length = 2, pattern = \"^\\\\w+$\" }\n\n\
+             [[param]]\ncode = { ask = \"Code?\", default = \"abcd\", min_length = 3, max_length = 5 }\n\n\
+             [project.name_rules]\npattern = \"^[A-Z]\"\n",
+            "$param.crate_name$ $param.workers$ $param.tags$ $param.code$",
+        );
+        let readme = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(readme.trim_end(), "hello 4  abcd");
+
+        for (key, value, expected) in [
+            (
+                "crate_name",
+                "Hello",
+                "invalid value `Hello` for `crate_name`, must be lowercase snake_case (from -P:crate_name)",
+            ),
+            ("workers", "0", "invalid value `0` for `workers`, expected at least 1"),
+            ("workers", "100", "invalid value `100` for `workers`, expected at most 64"),
+            ("tags", "a, b, c", "invalid value `a, b, c` for `tags`, expected at most 2 items"),
+            ("tags", "a, b-c", "invalid value `a, b-c` for `tags`, expected to match `^\\w+$`"),
+            ("code", "ab", "invalid value `ab` for `code`, expected at least 3 characters"),
+            ("name", "hello", "invalid value `hello` for `name`, expected to match `^[A-Z]` (from -P:name)"),
+        ] {
+            let err = generate_quiet(
+                &dir,
+                vec![Param::new(key, value)],
+                HashMap::new(),
+                HashMap::new(),
+            )
+            .unwrap_err();
+            assert!(err.to_string().starts_with(expected), "{}", err);
+        }
+        // refused before writing anything
+        let _ = fs::remove_dir_all(dir.join("out"));
+        let err = generate_quiet(
+            &dir,
+            vec![Param::new("workers", "x")],
+            HashMap::new(),
+            HashMap::new(),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("expected an integer (from -P:workers)"));
+        assert!(!dir.join("out").exists());
+
+        let tpl = dir.join("template");
+        let config = fs::read_to_string(tpl.join("Reframe.toml")).unwrap();
+        for (from, to, expected) in [
+            (
+                "min_length = 3",
+                "min_length = \"x\"",
+                "invalid rules of `code`: ",
+            ),
+            (
+                "pattern = \"^[a-z][a-z0-9_]*$\"",
+                "pattern = \"^[a-z\"",
+                "invalid pattern of `crate_name`: ",
+            ),
+            (
+                "pattern = \"^[A-Z]\"",
+                "pattern = \"^[A-Z\"",
+                "invalid pattern of `name`: ",
+            ),
+        ] {
+            fs::write(tpl.join("Reframe.toml"), config.replace(from, to)).unwrap();
+            let err = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().starts_with(expected), "{}", err);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_bool_params() {
+        let dir = make_template(
+            "bool_params",
+            "[[param]]\nwith_x = { ask = \"With x?\", default = true }\n\n\
+             [[param]]\nwith_y = { ask = \"With y?\", type = \"bool\", default = \"no\" }\n",
+            "# <% if param.with_x %>\nx\n# <% endif %>\n{{#if with_y}}y{{/if}}$param.with_x$ $param.with_y$",
+        );
+        let readme = generate_quiet(&dir, vec![], HashMap::new(), HashMap::new()).unwrap();
+        assert_eq!(readme.trim_end(), "x\ntrue false");
+
+        for (x, y) in [("no", "Yes"), ("n", "y"), ("0", "1"), ("FALSE", "true")] {
+            let params = vec![Param::new("with_x", x), Param::new("with_y", y)];
+            let readme = generate_quiet(&dir, params, HashMap::new(), HashMap::new()).unwrap();
+            assert_eq!(readme.trim_end(), "yfalse true");
+        }
+
+        let err = generate_quiet(
+            &dir,
+            vec![Param::new("with_x", "nope")],
+            HashMap::new(),
+            HashMap::new(),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid value `nope` for `with_x`, expected yes or no"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,346 @@
+//! Expressions of the `if` conditions, shared by params, `[[present]]` and
+//! template conditionals, eg:
+//!
+//! ```text
+//! with_serde
+//! db == 'postgres' && !with_docker
+//! db in ['postgres', 'mysql']
+//! len(features) > 0
+//! ```
+//!
+//! Unknown names evaluate to null, which is false.
+
+use serde_json::Value as JsonValue;
+
+use std::io;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(JsonValue),
+    Var(String),
+    List(Vec<Expr>),
+    Call(String, Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(&'static str, Box<Expr>, Box<Expr>),
+}
+
+const OPS: [&str; 16] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", "[", "]", ",", "=", "-",
+];
+
+fn invalid<S: AsRef<str>>(msg: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg.as_ref().to_string())
+}
+
+fn tokenize(text: &str) -> io::Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c == '\'' || c == '"' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| invalid(format!("unclosed string {}", rest)))?;
+            tokens.push(Token::Str(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let num = rest[..end]
+                .parse()
+                .map_err(|_| invalid(format!("invalid number `{}`", &rest[..end])))?;
+            tokens.push(Token::Num(num));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(*op)) {
+            if *op == "=" || *op == "-" {
+                Err(invalid(format!("unexpected `{}`", op)))?;
+            }
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            Err(invalid(format!("unexpected `{}`", c)))?;
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Op(o)) => *o == op,
+            Some(Token::Ident(word)) => word == op,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, op: &str) -> io::Result<()> {
+        if !self.eat(op) {
+            Err(invalid(format!("expected `{}`", op)))?;
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> io::Result<Expr> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> io::Result<Expr> {
+        let mut left = self.not()?;
+        while self.eat("&&") {
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> io::Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> io::Result<Expr> {
+        let left = self.primary()?;
+        for op in ["==", "!=", "<=", ">=", "<", ">", "in"] {
+            if self.eat(op) {
+                return Ok(Expr::Compare(op, Box::new(left), Box::new(self.primary()?)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn list(&mut self, close: &str) -> io::Result<Vec<Expr>> {
+        let mut items = vec![];
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.or()?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn primary(&mut self) -> io::Result<Expr> {
+        match self.next() {
+            Some(Token::Op("(")) => {
+                let expr = self.or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Op("[")) => Ok(Expr::List(self.list("]")?)),
+            Some(Token::Str(s)) => Ok(Expr::Literal(JsonValue::from(s))),
+            Some(Token::Num(n)) => Ok(Expr::Literal(JsonValue::from(n))),
+            Some(Token::Ident(word)) => match word.as_str() {
+                "true" => Ok(Expr::Literal(JsonValue::Bool(true))),
+                "false" => Ok(Expr::Literal(JsonValue::Bool(false))),
+                _ if self.eat("(") => {
+                    if word != "len" {
+                        Err(invalid(format!("unknown function `{}`", word)))?;
+                    }
+                    Ok(Expr::Call(word, self.list(")")?))
+                }
+                _ => Ok(Expr::Var(word)),
+            },
+            Some(Token::Op(op)) => Err(invalid(format!("unexpected `{}`", op))),
+            None => Err(invalid("unexpected end")),
+        }
+    }
+}
+
+/// Truthiness of value: null, false, `"false"`, empty string, zero and empty list are false.
+pub fn is_true(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Null => false,
+        JsonValue::Bool(b) => *b,
+        JsonValue::String(s) => !s.is_empty() && s != "false",
+        JsonValue::Number(n) => n.as_f64() != Some(0.0),
+        JsonValue::Array(a) => !a.is_empty(),
+        JsonValue::Object(o) => !o.is_empty(),
+    }
+}
+
+/// Number of value, strings holding a number count too.
+fn as_number(value: &JsonValue) -> Option<f64> {
+    match value {
+        JsonValue::Number(n) => n.as_f64(),
+        JsonValue::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Params values are strings, so `"true" == true` and `"1" == 1`.
+fn loose_eq(a: &JsonValue, b: &JsonValue) -> bool {
+    match (a, b) {
+        (JsonValue::String(s), JsonValue::Bool(v)) | (JsonValue::Bool(v), JsonValue::String(s)) => {
+            s == &v.to_string()
+        }
+        (JsonValue::Number(_), _) | (_, JsonValue::Number(_)) => {
+            match (as_number(a), as_number(b)) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            }
+        }
+        _ => a == b,
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> io::Result<Expr> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            Err(invalid(format!("unexpected {:?}", token)))?;
+        }
+        Ok(expr)
+    }
+
+    /// Names of the values referred by expression.
+    pub fn vars(&self) -> Vec<&str> {
+        match self {
+            Expr::Literal(_) => vec![],
+            Expr::Var(name) => vec![name.as_str()],
+            Expr::List(items) | Expr::Call(_, items) => items.iter().flat_map(Expr::vars).collect(),
+            Expr::Not(e) => e.vars(),
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Compare(_, a, b) => {
+                let mut vars = a.vars();
+                vars.extend(b.vars());
+                vars
+            }
+        }
+    }
+
+    pub fn eval<F: Fn(&str) -> Option<JsonValue>>(&self, lookup: &F) -> JsonValue {
+        match self {
+            Expr::Literal(v) => v.clone(),
+            Expr::Var(name) => lookup(name).unwrap_or(JsonValue::Null),
+            Expr::List(items) => JsonValue::Array(items.iter().map(|e| e.eval(lookup)).collect()),
+            Expr::Call(_, args) => match args.first().map(|e| e.eval(lookup)) {
+                Some(JsonValue::Array(a)) => JsonValue::from(a.len()),
+                Some(JsonValue::String(s)) => JsonValue::from(s.chars().count()),
+                _ => JsonValue::from(0),
+            },
+            Expr::Not(e) => JsonValue::Bool(!e.is_true(lookup)),
+            Expr::And(a, b) => JsonValue::Bool(a.is_true(lookup) && b.is_true(lookup)),
+            Expr::Or(a, b) => JsonValue::Bool(a.is_true(lookup) || b.is_true(lookup)),
+            Expr::Compare(op, a, b) => {
+                let (a, b) = (a.eval(lookup), b.eval(lookup));
+                let rv = match *op {
+                    "==" => loose_eq(&a, &b),
+                    "!=" => !loose_eq(&a, &b),
+                    "in" => match &b {
+                        JsonValue::Array(items) => items.iter().any(|item| loose_eq(&a, item)),
+                        JsonValue::String(s) => a.as_str().is_some_and(|a| s.contains(a)),
+                        _ => false,
+                    },
+                    op => match (as_number(&a), as_number(&b)) {
+                        (Some(a), Some(b)) => match op {
+                            "<" => a < b,
+                            "<=" => a <= b,
+                            ">" => a > b,
+                            _ => a >= b,
+                        },
+                        _ => false,
+                    },
+                };
+                JsonValue::Bool(rv)
+            }
+        }
+    }
+
+    pub fn is_true<F: Fn(&str) -> Option<JsonValue>>(&self, lookup: &F) -> bool {
+        is_true(&self.eval(lookup))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_eval() {
+        let values = json!({
+            "db": "postgres",
+            "with_docker": "false",
+            "with_ci": "true",
+            "port": "8080",
+            "features": ["auth", "admin"],
+            "param.name": "hello",
+        });
+        let lookup = |k: &str| values.get(k).cloned();
+        let check = |text: &str| Expr::parse(text).unwrap().is_true(&lookup);
+
+        assert!(check("with_ci"));
+        assert!(!check("with_docker"));
+        assert!(!check("unknown"));
+        assert!(check("db == 'postgres' && !with_docker"));
+        assert!(!check("db == \"mysql\" || with_docker"));
+        assert!(check("db != 'mysql'"));
+        assert!(check("db in ['postgres','mysql']"));
+        assert!(!check("db in ['sqlite']"));
+        assert!(check("'auth' in features"));
+        assert!(check("len(features) > 0 && len(features) <= 2"));
+        assert!(!check("len(unknown) > 0"));
+        assert!(check("port >= 1024"));
+        assert!(check("with_ci == true"));
+        assert!(check("!(with_docker || db == 'mysql')"));
+        assert!(check("param.name == 'hello'"));
+
+        assert_eq!(
+            Expr::parse("db == 'x' && !with_docker || len(features) > 0")
+                .unwrap()
+                .vars(),
+            vec!["db", "with_docker", "features"]
+        );
+
+        for text in ["", "db ==", "db = 'x'", "(db", "'x", "foo(db)", "db db"] {
+            assert!(Expr::parse(text).is_err(), "{}", text);
+        }
+    }
+}
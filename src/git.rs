@@ -0,0 +1,225 @@
+use log::debug;
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const GIT_URL_PREFIXES: &[&str] = &["ssh://", "git://", "file://", "git@"];
+
+/// Check whether source is a plain git url rather than a GitHub `owner/repo` name,
+/// eg: `https://git.example.com/x.git`, `ssh://git@host/x.git`, `file:///srv/x.git`.
+pub fn is_git_url(source: &str) -> bool {
+    let source = source.trim_end_matches('/');
+    !source.starts_with('-')
+        && (source.ends_with(".git") || GIT_URL_PREFIXES.iter().any(|p| source.starts_with(p)))
+}
+
+/// Refuse argument git would read as an option, eg: `--upload-pack=...`.
+fn check_arg(what: &str, arg: &str) -> io::Result<()> {
+    if arg.starts_with('-') {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid git {} `{}`", what, arg),
+        ))?;
+    }
+    Ok(())
+}
+
+/// Run git command, returns its standard output.
+fn run_git<P: AsRef<Path>>(args: &[&str], cwd: Option<P>) -> io::Result<String> {
+    let mut cmd = Command::new("git");
+    if let Some(cwd) = cwd {
+        cmd.current_dir(cwd);
+    }
+    debug!("git {}", args.join(" "));
+    let output = cmd
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, format!("cannot run git: {}", e)))?;
+
+    if !output.status.success() {
+        Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))?;
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Clone git repository `url` into `out_dir` and checkout `reference` if any,
+/// reference can be a branch, tag or commit.
+/// When no reference given the default branch (HEAD) of remote is used.
+pub fn clone<P: AsRef<Path>>(
+    url: &str,
+    reference: Option<&str>,
+    out_dir: P,
+) -> io::Result<PathBuf> {
+    let out_dir = out_dir.as_ref();
+    check_arg("url", url)?;
+    if let Some(reference) = reference {
+        check_arg("reference", reference)?;
+    }
+
+    if out_dir.exists() {
+        fs::remove_dir_all(out_dir)?;
+    }
+    if let Some(parent) = out_dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let out_dir_str = out_dir.to_string_lossy();
+
+    run_git(
+        &["clone", "--quiet", "--no-checkout", "--", url, &out_dir_str],
+        None::<&Path>,
+    )?;
+
+    let reference = reference.unwrap_or("HEAD");
+    run_git(&["checkout", "--quiet", reference, "--"], Some(out_dir)).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("cannot checkout `{}` from {}. {}", reference, url, e),
+        )
+    })?;
+
+    // reframe source doesn't need the history
+    fs::remove_dir_all(out_dir.join(".git"))?;
+
+    Ok(out_dir.to_path_buf())
+}
+
+/// Names of the tags of remote git repository `url`.
+pub fn ls_remote_tags(url: &str) -> io::Result<Vec<String>> {
+    check_arg("url", url)?;
+    let output = run_git(&["ls-remote", "--tags", "--refs", "--", url], None::<&Path>)?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .filter_map(|name| name.strip_prefix("refs/tags/"))
+        .map(|name| name.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use std::env;
+
+    fn git(args: &[&str], cwd: &Path) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(cwd)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn write_version(dir: &Path, version: &str) {
+        fs::write(
+            dir.join("Reframe.toml"),
+            format!("[project]\nversion = \"{}\"\n", version),
+        )
+        .unwrap();
+    }
+
+    /// Create bare repo with `master` at v3, branch `develop` at v2 and tag `v1.0.0` at v1.
+    /// Returns the bare repo path and the commit sha of v1.
+    fn setup_bare_repo(root: &Path) -> (PathBuf, String) {
+        let work = root.join("work");
+        fs::create_dir_all(&work).unwrap();
+        git(&["init", "--quiet", "--initial-branch=master"], &work);
+
+        write_version(&work, "1");
+        git(&["add", "."], &work);
+        git(&["commit", "--quiet", "-m", "v1"], &work);
+        git(&["tag", "v1.0.0"], &work);
+        let sha = git(&["rev-parse", "HEAD"], &work);
+
+        git(&["checkout", "--quiet", "-b", "develop"], &work);
+        write_version(&work, "2");
+        git(&["commit", "--quiet", "-am", "v2"], &work);
+
+        git(&["checkout", "--quiet", "master"], &work);
+        write_version(&work, "3");
+        git(&["commit", "--quiet", "-am", "v3"], &work);
+
+        let bare = root.join("template.rf.git");
+        git(
+            &["clone", "--quiet", "--bare", ".", &bare.to_string_lossy()],
+            &work,
+        );
+        (bare, sha)
+    }
+
+    fn read_version(dir: &Path) -> String {
+        fs::read_to_string(dir.join("Reframe.toml")).unwrap()
+    }
+
+    #[test]
+    fn test_is_git_url() {
+        assert!(is_git_url("https://git.example.com/templates/x.git"));
+        assert!(is_git_url("ssh://git@git.example.com/templates/x.git"));
+        assert!(is_git_url("git@github.com:anvie/basic-rust.rf.git"));
+        assert!(is_git_url("file:///srv/templates/x"));
+        assert!(!is_git_url("anvie/basic-rust"));
+        assert!(!is_git_url("./my-template"));
+        assert!(!is_git_url("--upload-pack=touch /tmp/pwned;.git"));
+    }
+
+    #[test]
+    fn test_clone_rejects_options() {
+        let root = env::temp_dir().join(format!(
+            "reframe_test_git_options_{}",
+            util::get_current_time_millis()
+        ));
+        let pwned = root.join("pwned");
+        let url = format!("--upload-pack=touch {};.git", pwned.display());
+
+        let err = clone(&url, None, root.join("out")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = clone("file:///srv/x.git", Some("--orphan=x"), root.join("out")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = ls_remote_tags(&url).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!pwned.exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_clone_local_bare_repo() {
+        let root = env::temp_dir().join(format!(
+            "reframe_test_git_{}",
+            util::get_current_time_millis()
+        ));
+        let (bare, sha) = setup_bare_repo(&root);
+        let url = format!("file://{}", bare.display());
+        let out = root.join("out");
+
+        clone(&url, None, &out).unwrap();
+        assert!(read_version(&out).contains("\"3\""));
+        assert!(!out.join(".git").exists());
+
+        clone(&url, Some("develop"), &out).unwrap();
+        assert!(read_version(&out).contains("\"2\""));
+
+        clone(&url, Some("v1.0.0"), &out).unwrap();
+        assert!(read_version(&out).contains("\"1\""));
+
+        clone(&url, Some(&sha), &out).unwrap();
+        assert!(read_version(&out).contains("\"1\""));
+
+        assert!(clone(&url, Some("no-such-ref"), &out).is_err());
+
+        assert_eq!(ls_remote_tags(&url).unwrap(), vec!["v1.0.0"]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
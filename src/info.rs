@@ -0,0 +1,234 @@
+use colored::*;
+use serde::Serialize;
+
+use crate::core::{Config, Param, PostGenerateOp, Present, ProjectConfig, ReframeConfig};
+
+use std::io;
+
+/// Everything a template declares in `Reframe.toml`, shown by `reframe info`.
+#[derive(Debug, Serialize)]
+pub struct TemplateInfo<'a> {
+    pub reframe: &'a ReframeConfig,
+    pub project: &'a ProjectConfig,
+    pub params: Vec<Param>,
+    pub presents: &'a [Present],
+    pub post_generate: &'a [PostGenerateOp],
+}
+
+impl<'a> TemplateInfo<'a> {
+    pub fn new(config: &'a Config) -> io::Result<Self> {
+        Ok(TemplateInfo {
+            reframe: &config.reframe,
+            project: &config.project,
+            params: config.params()?,
+            presents: &config.presents,
+            post_generate: &config.post_generate,
+        })
+    }
+
+    pub fn to_json(&self) -> io::Result<String> {
+        serde_json::to_string_pretty(self).map_err(io::Error::other)
+    }
+
+    /// Markdown document, ready to paste into the template README.
+    pub fn to_markdown(&self) -> String {
+        let mut md = vec![
+            format!("## {}", self.reframe.name),
+            String::new(),
+            format!("* Author: {}", self.reframe.author),
+            format!("* Minimum Reframe version: {}", self.reframe.min_version),
+            format!(
+                "* Project defaults: name `{}`, version `{}`",
+                self.project.name, self.project.version
+            ),
+            String::new(),
+        ];
+
+        if !self.params.is_empty() {
+            md.push("### Parameters".to_string());
+            md.push(String::new());
+            md.push("| Key | Question | Type | Default | Options | Condition |".to_string());
+            md.push("|-----|----------|------|---------|---------|-----------|".to_string());
+            for p in self.params.iter() {
+                md.push(format!(
+                    "| `{}` | {} | {:?} | {} | {} | {} |",
+                    p.key,
+                    escape_cell(&p.ask),
+                    p.kind,
+                    code(p.default.as_deref()),
+                    p.options
+                        .iter()
+                        .map(|a| format!("`{}`", a))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    code(p.ifwith.as_deref())
+                ));
+            }
+            md.push(String::new());
+        }
+
+        if !self.presents.is_empty() {
+            md.push("### Conditional files".to_string());
+            md.push(String::new());
+            md.push("| Path | Condition |".to_string());
+            md.push("|------|-----------|".to_string());
+            for present in self.presents.iter() {
+                md.push(format!(
+                    "| `{}` | `{}` |",
+                    present.path,
+                    escape_cell(&present.ifcond)
+                ));
+            }
+            md.push(String::new());
+        }
+
+        let executables: Vec<&str> = self
+            .post_generate
+            .iter()
+            .filter_map(|op| op.make_executable.as_deref())
+            .collect();
+        if !executables.is_empty() {
+            md.push("### Post generate".to_string());
+            md.push(String::new());
+            for path in executables {
+                md.push(format!("* make executable `{}`", path));
+            }
+            md.push(String::new());
+        }
+
+        md.join("\n")
+    }
+
+    /// Print human readable info to stdout.
+    pub fn print(&self) {
+        println!(
+            " {} by {}",
+            self.reframe.name.bright_blue().bold(),
+            self.reframe.author
+        );
+        println!(" Minimum Reframe version: {}", self.reframe.min_version);
+        println!(
+            " Project defaults: name `{}`, version `{}`",
+            self.project.name.yellow(),
+            self.project.version.yellow()
+        );
+        println!();
+
+        if !self.params.is_empty() {
+            println!(" Parameters:");
+            println!();
+            for p in self.params.iter() {
+                println!("  * {} ({:?})", p.key.bright_blue(), p.kind);
+                println!("      question : {}", p.ask);
+                if let Some(default) = p.default.as_ref() {
+                    println!("      default  : {}", default.yellow());
+                }
+                if !p.options.is_empty() {
+                    println!("      options  : {}", p.options.join("/"));
+                }
+                if let Some(ifwith) = p.ifwith.as_ref() {
+                    println!("      if       : {}", ifwith);
+                }
+            }
+            println!();
+        }
+
+        if !self.presents.is_empty() {
+            println!(" Conditional files:");
+            println!();
+            for present in self.presents.iter() {
+                println!("  * {} if {}", present.path, present.ifcond);
+            }
+            println!();
+        }
+
+        let executables: Vec<&str> = self
+            .post_generate
+            .iter()
+            .filter_map(|op| op.make_executable.as_deref())
+            .collect();
+        if !executables.is_empty() {
+            println!(" Post generate:");
+            println!();
+            for path in executables {
+                println!("  * make executable {}", path);
+            }
+            println!();
+        }
+    }
+}
+
+fn code(value: Option<&str>) -> String {
+    value
+        .map(|a| format!("`{}`", escape_cell(a)))
+        .unwrap_or_default()
+}
+
+fn escape_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            [reframe]
+            name = "Basic Rust"
+            author = "robin@ansvia.com"
+            min_version = "0.5.0"
+
+            [project]
+            name = "Hello World"
+            version = "1.0"
+
+            [[param]]
+            with_serde = { ask = "Dengan serde?", default = false }
+
+            [[param]]
+            serde_version = { ask = "Versi serde?", default = "1.0", if = "with_serde" }
+
+            [[param]]
+            license = { ask = "License?", default = "MIT", options = ["MIT", "Apache-2.0"] }
+
+            [[present]]
+            path = "src/serde.rs"
+            if = "with_serde"
+
+            [[post_generate]]
+            make_executable = "run.sh"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_info_json() {
+        let config = config();
+        let json: serde_json::Value =
+            serde_json::from_str(&TemplateInfo::new(&config).unwrap().to_json().unwrap()).unwrap();
+        assert_eq!(json["reframe"]["min_version"], "0.5.0");
+        assert_eq!(json["project"]["name"], "Hello World");
+        assert_eq!(json["params"][0]["key"], "with_serde");
+        assert_eq!(json["params"][0]["kind"], "Bool");
+        assert_eq!(json["params"][1]["if"], "with_serde");
+        assert_eq!(json["params"][2]["options"][1], "Apache-2.0");
+        assert_eq!(json["presents"][0]["if"], "with_serde");
+        assert_eq!(json["post_generate"][0]["make_executable"], "run.sh");
+    }
+
+    #[test]
+    fn test_info_markdown() {
+        let config = config();
+        let md = TemplateInfo::new(&config).unwrap().to_markdown();
+        assert!(md.starts_with("## Basic Rust\n"));
+        assert!(
+            md.contains("| `serde_version` | Versi serde? | String | `1.0` |  | `with_serde` |")
+        );
+        assert!(md.contains("| `license` | License? | Options | `MIT` | `MIT`, `Apache-2.0` |  |"));
+        assert!(md.contains("| `src/serde.rs` | `with_serde` |"));
+        assert!(md.contains("* make executable `run.sh`"));
+    }
+}
@@ -1,5 +1,19 @@
+pub mod answers;
+pub mod archive;
+pub mod cache;
 pub mod core;
+pub mod expr;
+pub mod git;
+pub mod info;
+pub mod lockfile;
+pub mod memory;
+pub mod registry;
+pub mod source;
+pub mod user_config;
 mod util;
+pub mod version;
 
 #[cfg(test)]
 mod test_handlebars;
+#[cfg(test)]
+mod test_util;
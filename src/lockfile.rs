@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+use std::{fs, io, path::Path};
+
+/// Default lockfile name, looked up in the current dir.
+pub const LOCKFILE_NAME: &str = "reframe.lock";
+
+/// Pinned archive digest of a source, and the tag resolved from version requirement.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LockEntry {
+    pub source: String,
+    /// Version requirement resolved to `reference`, eg: `^1.2`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Pinned sources, eg:
+///
+/// ```toml
+/// [[source]]
+/// source = "github.com/anvie/basic-rust.rf"
+/// version = "^1.0"
+/// reference = "v1.0.0"
+/// sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default, rename = "source")]
+    pub sources: Vec<LockEntry>,
+}
+
+impl Lockfile {
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let f = fs::read(path)?;
+        let rv = String::from_utf8_lossy(f.as_slice());
+        toml::from_str(&rv).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Read lockfile, missing file means empty lockfile.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Self::default());
+        }
+        Self::read(&path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("invalid lockfile `{}`: {}", path.as_ref().display(), e),
+            )
+        })
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let data = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, data)
+    }
+
+    pub fn find(&self, source: &str, reference: Option<&str>) -> Option<&LockEntry> {
+        self.sources
+            .iter()
+            .find(|e| e.source == source && e.reference.as_deref() == reference)
+    }
+
+    /// Add or update the pinned digest of `source` at `reference`.
+    pub fn pin(&mut self, source: &str, reference: Option<&str>, sha256: &str) {
+        let mut pinned = false;
+        for entry in self
+            .sources
+            .iter_mut()
+            .filter(|e| e.source == source && e.reference.as_deref() == reference)
+        {
+            entry.sha256 = Some(sha256.to_string());
+            pinned = true;
+        }
+        if !pinned {
+            self.sources.push(LockEntry {
+                source: source.to_string(),
+                version: None,
+                reference: reference.map(|a| a.to_string()),
+                sha256: Some(sha256.to_string()),
+            });
+        }
+    }
+
+    /// Entry of `source` with the tag resolved from version `requirement`.
+    pub fn find_version(&self, source: &str, requirement: &str) -> Option<&LockEntry> {
+        self.sources
+            .iter()
+            .find(|e| e.source == source && e.version.as_deref() == Some(requirement))
+    }
+
+    /// Record version `requirement` of `source` resolved to tag `reference`,
+    /// the digest pinned for the previous tag is dropped.
+    pub fn pin_version(&mut self, source: &str, requirement: &str, reference: &str) {
+        if let Some(entry) = self
+            .sources
+            .iter_mut()
+            .find(|e| e.source == source && e.version.as_deref() == Some(requirement))
+        {
+            if entry.reference.as_deref() != Some(reference) {
+                entry.reference = Some(reference.to_string());
+                entry.sha256 = None;
+            }
+            return;
+        }
+        match self.sources.iter_mut().find(|e| {
+            e.source == source && e.version.is_none() && e.reference.as_deref() == Some(reference)
+        }) {
+            Some(entry) => entry.version = Some(requirement.to_string()),
+            None => self.sources.push(LockEntry {
+                source: source.to_string(),
+                version: Some(requirement.to_string()),
+                reference: Some(reference.to_string()),
+                sha256: None,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use std::env;
+
+    #[test]
+    fn test_pin_and_find() {
+        let path = env::temp_dir().join(format!(
+            "reframe_test_lockfile_{}.lock",
+            util::get_current_time_millis()
+        ));
+        let mut lockfile = Lockfile::load(&path).unwrap();
+        assert!(lockfile.sources.is_empty());
+
+        lockfile.pin("github.com/anvie/basic-rust.rf", Some("v1"), "aa");
+        lockfile.pin("github.com/anvie/basic-rust.rf", None, "bb");
+        lockfile.pin("github.com/anvie/basic-rust.rf", Some("v1"), "cc");
+        lockfile.write(&path).unwrap();
+
+        let lockfile = Lockfile::load(&path).unwrap();
+        assert_eq!(lockfile.sources.len(), 2);
+        assert_eq!(
+            lockfile
+                .find("github.com/anvie/basic-rust.rf", Some("v1"))
+                .and_then(|e| e.sha256.as_deref()),
+            Some("cc")
+        );
+        assert_eq!(
+            lockfile
+                .find("github.com/anvie/basic-rust.rf", None)
+                .and_then(|e| e.sha256.as_deref()),
+            Some("bb")
+        );
+        assert!(lockfile.find("gitlab.com/group/repo", None).is_none());
+
+        let mut lockfile = lockfile;
+        lockfile.pin_version("github.com/anvie/basic-rust.rf", "^1.0", "v1");
+        assert_eq!(lockfile.sources.len(), 2);
+        lockfile.pin_version("github.com/anvie/basic-rust.rf", "^1.0", "v1.1");
+        lockfile.write(&path).unwrap();
+
+        let lockfile = Lockfile::load(&path).unwrap();
+        let entry = lockfile
+            .find_version("github.com/anvie/basic-rust.rf", "^1.0")
+            .unwrap();
+        assert_eq!(entry.reference.as_deref(), Some("v1.1"));
+        assert_eq!(entry.sha256, None);
+
+        let _ = fs::remove_file(&path);
+    }
+}
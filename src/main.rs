@@ -13,18 +13,44 @@ extern crate regex;
 extern crate reqwest;
 extern crate zip;
 
+mod answers;
+mod archive;
+mod cache;
 mod core;
+mod expr;
+mod git;
+mod info;
+mod lockfile;
+mod memory;
+mod registry;
+mod source;
+mod user_config;
 mod util;
+mod version;
+
+#[cfg(test)]
+mod test_util;
 
 use colored::*;
 use rustyline::Editor;
 
 use std::{
     env,
-    path::{Path, PathBuf},
+    io::{self, IsTerminal},
+    path::Path,
 };
 
-use crate::core::{Param, Reframe};
+use crate::{
+    answers::AnswersFile,
+    cache::Cache,
+    core::{env_params, Param, Reframe},
+    info::TemplateInfo,
+    lockfile::Lockfile,
+    memory::AnswerMemory,
+    registry::RegistryEntry,
+    source::{FetchOptions, Source, SourceSpec},
+    user_config::UserConfig,
+};
 
 fn print_usage(args: &[String]) {
     let path = Path::new(&args[0]);
@@ -32,26 +58,101 @@ fn print_usage(args: &[String]) {
     println!("Usage: ");
     println!("       ");
     println!("       $ {} [SOURCE] [OPTIONS]", exe_name);
+    println!("       $ {} cache [list|clean]", exe_name);
+    println!("       $ {} info [SOURCE] [--json|--markdown]", exe_name);
+    println!(
+        "       $ {} search [TERMS] [--tag TAG] [--refresh]",
+        exe_name
+    );
+    println!();
+    println!("       Without SOURCE on a terminal, pick one from the registry index.");
     println!();
     println!("OPTIONS:");
     println!();
-    println!("       -L,--list          List available sources.");
+    println!("       -L,--list          List available sources, or templates inside SOURCE.");
+    println!("       --registry         Registry index url or file to list, can be repeated.");
     println!("       --dry-run          Test only, don't touch disk.");
     println!("       -P:[key]=[value]   Preset parameters.");
-    println!("       -b,--branch        Select branch to use. Default: master");
+    println!("                          Env vars REFRAME_PARAM_[KEY], REFRAME_NAME and");
+    println!("                          REFRAME_VERSION preset parameters too.");
+    println!("       -b,--branch        Select branch, tag or commit to use. Default: `branch`");
+    println!("                          of the user config, or the default branch of source.");
+    println!("       --subdir           Use template in sub-directory of the source.");
     println!("       ");
     println!(
         "       --out              Custom output dir name (default: project name in kebab case)."
     );
     println!("       --quiet            Don't ask anything, just do it.");
+    println!("       --answers          Answers file (TOML or JSON) presetting parameters.");
+    println!("       --save-answers     Write the answers to file to replay the generation.");
+    println!("       --no-remember      Don't use nor record the answers given last time.");
+    println!("       -v,--verbose       Show where every answer came from.");
+    println!("       --offline          Only use cached sources, don't touch network.");
+    println!("       --sha256           Refuse source archive not matching the SHA-256 digest.");
+    println!("       --lock             Pin the source archive digest in the lockfile and");
+    println!("                          resolve version requirement again, resolved versions");
+    println!("                          are always recorded in the lockfile.");
+    println!(
+        "       --lockfile         Lockfile to read pinned digests from. Default: reframe.lock"
+    );
     println!();
     println!("Examples:");
     println!();
     println!("       $ {} anvie/basic-rust", exe_name);
     println!("       $ {} anvie/basic-rust --dry-run", exe_name);
+    println!("       $ {} anvie/basic-rust@v1.2.0", exe_name);
+    println!("       $ {} anvie/basic-rust#1a2b3c4", exe_name);
+    println!("       $ {} anvie/basic-rust@^1.2", exe_name);
+    println!("       $ {} ourorg/templates//rust-service", exe_name);
+    println!("       $ {} ourorg/templates --list", exe_name);
+    println!("       $ {} search grpc --tag rust", exe_name);
+    println!(
+        "       $ {} https://git.example.com/templates/x.git --branch=v1.0",
+        exe_name
+    );
+    println!("       $ {} gitlab:group/repo", exe_name);
+    println!("       $ {} gitea:git.example.com/owner/repo", exe_name);
+    println!("       $ {} bitbucket:owner/repo", exe_name);
+    println!("       $ {} https://example.com/template.tar.gz", exe_name);
+    println!("       $ {} ./template.zip", exe_name);
+    println!(
+        "       $ cat template.tar.gz | {} - -P:name=hello",
+        exe_name
+    );
     println!();
 }
 
+fn print_registry_entry(entry: &RegistryEntry, marker: &str, show_registry: bool) {
+    let indent = " ".repeat(marker.len());
+    print!(" {} {: <30} - {}", marker, entry.name, entry.description);
+    if !entry.tags.is_empty() {
+        print!(" [{}]", entry.tags.join(", "));
+    }
+    if let (false, Some(min_version)) = (entry.is_supported(), entry.min_version.as_ref()) {
+        print!(
+            " {}",
+            format!("(requires Reframe {})", min_version).yellow()
+        );
+    }
+    println!();
+    if entry.source != entry.name || entry.default_ref.is_some() {
+        println!(
+            " {} {: <30}   $ reframe {}",
+            indent,
+            "",
+            entry.source_spec()
+        );
+    }
+    if show_registry {
+        println!(
+            " {} {: <30}   {}",
+            indent,
+            "",
+            format!("from {}", entry.registry).bright_black()
+        );
+    }
+}
+
 // extract user's arguments to params
 fn extract_params(args: &[String]) -> Vec<Param> {
     args.iter()
@@ -68,33 +169,104 @@ fn extract_params(args: &[String]) -> Vec<Param> {
 async fn main() {
     env_logger::init();
 
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
 
     if args.contains(&"--version".to_string()) {
         println!(" Reframe {}", env!("CARGO_PKG_VERSION"));
         return;
     }
 
-    println!();
-    println!(" Reframe {}", env!("CARGO_PKG_VERSION"));
-    println!(" project generator tool");
-    println!(" by: Robin Syihab <r@ansvia.com>");
-    println!(" Twitter: @anvie");
-    println!(" ---------------------------");
-    println!();
+    let info = args.get(1).map(|a| a.as_str()) == Some("info");
+    let json_output = args.contains(&"--json".to_string());
+    let markdown_output = args.contains(&"--markdown".to_string());
+    // keep stdout clean for machine readable info
+    let plain_output = info && (json_output || markdown_output);
+
+    if !plain_output {
+        println!();
+        println!(" Reframe {}", env!("CARGO_PKG_VERSION"));
+        println!(" project generator tool");
+        println!(" by: Robin Syihab <r@ansvia.com>");
+        println!(" Twitter: @anvie");
+        println!(" ---------------------------");
+        println!();
+    }
+
+    // without source the user picks one from the registry, when there is someone to ask
+    let pick = args.len() < 2 && io::stdin().is_terminal() && io::stdout().is_terminal();
 
-    if args.len() < 2 || args[1] == "--help" {
+    if (args.len() < 2 && !pick)
+        || args.get(1).map(|a| a.as_str()) == Some("--help")
+        || (info && args.len() < 3)
+    {
         print_usage(&args);
         return;
     }
 
+    if args.get(1).map(|a| a.as_str()) == Some("cache") {
+        cache_command(&args, &Cache::user_default());
+        return;
+    }
+
     let list_sources = args.contains(&"-L".to_string()) || args.contains(&"--list".to_string());
 
-    if list_sources {
+    let user_config = match UserConfig::load() {
+        Ok(user_config) => user_config,
+        Err(e) => {
+            eprintln!("😭 {} {}", "FAILED:".red(), e);
+            eprintln!();
+            return;
+        }
+    };
+
+    if let Some(color) = user_config.color {
+        colored::control::set_override(color);
+    }
+
+    // registries from command line come first
+    let mut registries = get_param_values(&args, "--registry");
+    registries.extend(user_config.registries());
+
+    if pick {
+        match pick_source(&Cache::user_default(), &registries).await {
+            Ok(Some(spec)) => args.push(spec),
+            Ok(None) => {
+                println!("aborted.");
+                return;
+            }
+            Err(e) => {
+                eprintln!("😭 {} {}", "FAILED:".red(), e);
+                eprintln!();
+                return;
+            }
+        }
+    }
+
+    if args[1] == "search" {
+        search_command(&args, &Cache::user_default(), &registries).await;
+        return;
+    }
+
+    if list_sources && args[1].starts_with('-') && args[1] != "-" {
+        let (entries, errors) = registry::get_available_sources(&registries).await;
+        for (location, e) in errors.iter() {
+            eprintln!(
+                " {} cannot query registry `{}`: {}",
+                "WARNING:".yellow(),
+                location,
+                e
+            );
+        }
+        if entries.is_empty() && !errors.is_empty() {
+            eprintln!("😭 {} no registry available", "FAILED:".red());
+            eprintln!();
+            return;
+        }
+
         println!(" Available sources:");
         println!();
-        for (name, description) in util::get_available_sources().await.unwrap() {
-            println!(" * {0: <30} - {1: <10}", name, description);
+        for entry in entries {
+            print_registry_entry(&entry, "*", registries.len() > 1);
         }
         println!();
         return;
@@ -106,26 +278,156 @@ async fn main() {
         debug!("DRY RUN MODE");
     }
 
-    let source = &args[1];
-    let branch = get_param_value(&args, "--branch", "-b").unwrap_or_else(|| "master".to_string());
+    let source = if info { &args[2] } else { &args[1] };
+    // local path wins over alias of the same name
+    let source = &if Path::new(source).exists() {
+        source.to_owned()
+    } else {
+        user_config.resolve_alias(source)
+    };
+    let branch = get_param_value(&args, "--branch", "-b");
+
+    let offline = args.contains(&"--offline".to_string());
+    let cache = Cache::user_default();
 
-    let reframe_work_path = env::temp_dir().join("reframe_work");
-    let source_path = if !Path::new(&source).exists() {
-        debug!("source not found in local: {}", source);
-        debug!("trying get from github.com/{} ...", source);
-        if branch != "master" {
-            println!(" Downloading from repo `{}` branch `{}`...", source, branch);
-        }else{
-            println!(" Downloading from repo `{}`...", source);
+    let spec = match SourceSpec::parse(source) {
+        Ok(spec) => spec,
+        Err(e) => {
+            eprintln!("😭 {} {}", "FAILED:".red(), e);
+            eprintln!();
+            return;
         }
-        let url = format!(
-            "https://github.com/{}.rf/archive/{}.zip?nocache={}",
-            source,
-            branch,
-            util::get_current_time_millis()
-        );
-        debug!("output: {}", env::temp_dir().display());
-        if let Err(e) = util::download(&url, &reframe_work_path, &format!("{}.zip", branch)).await {
+    };
+
+    let branch = match (spec.reference, branch) {
+        (Some(a), Some(b)) if a != b => {
+            eprintln!(
+                "😭 {} conflicting reference `{}` and --branch `{}`",
+                "FAILED:".red(),
+                a,
+                b
+            );
+            eprintln!();
+            return;
+        }
+        (a, b) => a.or(b).or_else(|| user_config.branch.clone()),
+    };
+    let subdir = match (spec.subdir, get_param_value(&args, "--subdir", "")) {
+        (Some(a), Some(b)) if a != b => {
+            eprintln!(
+                "😭 {} conflicting sub-directory `{}` and --subdir `{}`",
+                "FAILED:".red(),
+                a,
+                b
+            );
+            eprintln!();
+            return;
+        }
+        (a, b) => a.or(b),
+    };
+    let src = spec.source;
+
+    let lockfile_path = get_param_value(&args, "--lockfile", "")
+        .unwrap_or_else(|| lockfile::LOCKFILE_NAME.to_string());
+    let mut lockfile = match Lockfile::load(&lockfile_path) {
+        Ok(lockfile) => lockfile,
+        Err(e) => {
+            eprintln!("😭 {} {}", "FAILED:".red(), e);
+            eprintln!();
+            return;
+        }
+    };
+    let lock = args.contains(&"--lock".to_string());
+
+    // version requirement, eg: `^1.2`, is resolved to the highest matching tag
+    // unless locked already
+    let requirement = branch.clone().filter(|b| version::is_requirement(b));
+    let mut resolved = false;
+    let branch = match requirement.as_deref() {
+        Some(requirement) => {
+            let locked = lockfile
+                .find_version(&src.location(), requirement)
+                .and_then(|e| e.reference.to_owned())
+                .filter(|_| !lock);
+            let tag = match locked {
+                Some(tag) => {
+                    if !plain_output {
+                        println!(
+                            " Using `{}` for `{}`, locked in `{}`",
+                            tag, requirement, lockfile_path
+                        );
+                    }
+                    tag
+                }
+                None => {
+                    let opts = FetchOptions {
+                        cache: &cache,
+                        offline,
+                        user_config: &user_config,
+                        sha256: None,
+                    };
+                    match src.resolve_version(requirement, &opts).await {
+                        Ok(tag) => {
+                            if !plain_output {
+                                println!(" Resolved `{}` to `{}`", requirement, tag);
+                            }
+                            resolved = true;
+                            tag
+                        }
+                        Err(e) => {
+                            eprintln!("😭 {} {}", "FAILED:".red(), e);
+                            eprintln!();
+                            return;
+                        }
+                    }
+                }
+            };
+            Some(tag)
+        }
+        None => branch,
+    };
+
+    let sha256 = get_param_value(&args, "--sha256", "").or_else(|| {
+        lockfile
+            .find(&src.location(), branch.as_deref())
+            .and_then(|e| e.sha256.to_owned())
+    });
+    if let Some(sha256) = sha256.as_ref() {
+        if !util::is_sha256(sha256) {
+            eprintln!("😭 {} invalid sha256 digest `{}`", "FAILED:".red(), sha256);
+            eprintln!();
+            return;
+        }
+    }
+
+    match (&src, branch.as_ref()) {
+        _ if plain_output => (),
+        (Source::Local(_), _) => (),
+        (Source::Archive(_), _) => println!(" Extracting `{}`...", src.location()),
+        (Source::Stdin, _) => println!(" Reading archive from stdin..."),
+        (_, _) if offline => println!(" Using cached copy of `{}`...", src.location()),
+        (Source::Git(_), Some(branch)) => {
+            println!(" Cloning from `{}` at `{}`...", src.location(), branch)
+        }
+        (Source::Git(_), None) => println!(" Cloning from `{}`...", src.location()),
+        (Source::Remote(_), Some(branch)) => println!(
+            " Downloading from repo `{}` branch `{}`...",
+            src.location(),
+            branch
+        ),
+        (Source::Remote(_), None) => println!(" Downloading from repo `{}`...", src.location()),
+    }
+
+    debug!("cache: {}", cache.root().display());
+    let fetch_opts = FetchOptions {
+        cache: &cache,
+        offline,
+        user_config: &user_config,
+        sha256: sha256.as_deref(),
+    };
+    let fetched = match src.fetch(branch.as_deref(), &fetch_opts).await {
+        Ok(fetched) => fetched,
+        Err(e) => {
             eprintln!(
                 "😭 {} {}, while pulling from repo for `{}`",
                 "FAILED:".red(),
@@ -135,50 +437,180 @@ async fn main() {
             eprintln!();
             return;
         }
-        let _path = reframe_work_path.join(format!(
-            "{}.rf-master",
-            source.split('/').skip(1).collect::<String>()
-        ));
+    };
 
-        if _path.exists() {
-            _path
-        }else{
-            // change -master with -main as the default branch
-            reframe_work_path.join(format!(
-                "{}.rf-main",
-                source.split('/').skip(1).collect::<String>()
-            ))
+    if let (Some(digest), false) = (fetched.sha256.as_ref(), plain_output) {
+        println!(" Source sha256: {}", digest);
+    }
+    // resolved version is always recorded so the generation can be reproduced
+    let record = resolved && !dry_run;
+    if record || (lock && (requirement.is_some() || fetched.sha256.is_some())) {
+        if let (Some(requirement), Some(tag)) = (requirement.as_deref(), branch.as_deref()) {
+            lockfile.pin_version(&src.location(), requirement, tag);
         }
-    } else {
-        PathBuf::from(&source)
-    };
+        if let (Some(digest), true) = (fetched.sha256.as_ref(), lock) {
+            lockfile.pin(&src.location(), branch.as_deref(), digest);
+        }
+        if let Err(e) = lockfile.write(&lockfile_path) {
+            eprintln!(
+                "😭 {} cannot write `{}`: {}",
+                "FAILED:".red(),
+                lockfile_path,
+                e
+            );
+            eprintln!();
+            return;
+        }
+        if lock {
+            println!(" Pinned `{}` in `{}`", src.location(), lockfile_path);
+        } else if !plain_output {
+            println!(" Recorded the resolved version in `{}`", lockfile_path);
+        }
+    }
+    let source_path = fetched.root;
 
-    let mut rl = Editor::<()>::new()
-        .unwrap_or_else(|_| panic!("Unable to create editor: {}", "Rustyline".red()));
+    if list_sources {
+        list_templates(&source_path, &src.location());
+        return;
+    }
 
-    let history_path = env::temp_dir().join(".reframe~");
+    let source_path = match source::select_subdir(&source_path, subdir.as_deref()) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("😭 {} {}", "FAILED:".red(), e);
+            eprintln!();
+            return;
+        }
+    };
 
-    if rl.load_history(&history_path).is_err() {
-        debug!("no history");
+    if info {
+        let config = match core::read_config(source_path.join("Reframe.toml")) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("😭 {} cannot read `Reframe.toml`: {}", "FAILED:".red(), e);
+                eprintln!();
+                return;
+            }
+        };
+        let info = match TemplateInfo::new(&config) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("😭 {} {}", "FAILED:".red(), e);
+                eprintln!();
+                return;
+            }
+        };
+        if json_output {
+            match info.to_json() {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("😭 {} {}", "FAILED:".red(), e),
+            }
+        } else if markdown_output {
+            println!("{}", info.to_markdown());
+        } else {
+            info.print();
+        }
+        return;
     }
 
+    let mut rl = Editor::<()>::new()
+        .unwrap_or_else(|_| panic!("Unable to create editor: {}", "Rustyline".red()));
+
     let params = extract_params(&args);
 
     let mut rf = match Reframe::open(&source_path, &mut rl, dry_run, params) {
         Ok(rf) => rf,
         Err(e) => {
-            eprintln!("Cannot open reframe source in tmp dir. {}", format!("{}", e).red());
+            eprintln!(
+                "Cannot open reframe source in tmp dir. {}",
+                format!("{}", e).red()
+            );
             std::process::exit(2);
         }
     };
+    rf.set_defaults(user_config.defaults.clone());
+    rf.set_env_params(env_params(env::vars()));
+
+    // answers are remembered per template, regardless of the reference
+    let memory = (!dry_run
+        && !args.contains(&"--no-remember".to_string())
+        && user_config.remember_answers != Some(false))
+    .then(AnswerMemory::user_default);
+    let template_id = match &src {
+        Source::Local(_) => source_path
+            .canonicalize()
+            .unwrap_or_else(|_| source_path.to_owned())
+            .display()
+            .to_string(),
+        _ => match subdir.as_ref() {
+            Some(subdir) => format!("{}//{}", src.location(), subdir),
+            None => src.location(),
+        },
+    };
+    if let Some(memory) = memory.as_ref() {
+        rf.set_remembered(memory.recall(&template_id));
+    }
+
+    if let Some(path) = get_param_value(&args, "--answers", "") {
+        match AnswersFile::load(&path) {
+            Ok(answers) => rf.set_file_params(answers.params()),
+            Err(e) => {
+                eprintln!("😭 {} {}", "FAILED:".red(), e);
+                eprintln!();
+                return;
+            }
+        }
+    }
+
+    debug!(
+        "using source `{}` by {}",
+        rf.config.reframe.name, rf.config.reframe.author
+    );
 
     // get custom pre-out-name if any
     let pre_out_name: Option<String> = get_param_value(&args, "--out", "");
 
-    let quiet = args.contains(&"--quiet".to_string());
+    let mut quiet = args.contains(&"--quiet".to_string()) || user_config.quiet;
+    // stdin is taken by the archive, nothing left to read the answers from
+    if !quiet && matches!(src, Source::Stdin) {
+        println!(" Nothing is asked, params take their default (preset the others with -P).");
+        quiet = true;
+    }
+    let verbose = args.contains(&"--verbose".to_string()) || args.contains(&"-v".to_string());
 
     match rf.generate(".", pre_out_name, quiet) {
         Ok(Some(out_name)) => {
+            if verbose {
+                println!();
+                println!(" Answers:");
+                for answer in rf.answers() {
+                    println!(
+                        "   {: <20} = {: <20} {}",
+                        answer.key,
+                        answer.value,
+                        format!("from {}", answer.source.describe(&answer.key)).bright_black()
+                    );
+                }
+            }
+            if let Some(memory) = memory.as_ref() {
+                if let Err(e) = memory.remember(&template_id, rf.answers()) {
+                    eprintln!("{} cannot remember answers: {}", "WARNING:".yellow(), e);
+                }
+            }
+            if let Some(path) = get_param_value(&args, "--save-answers", "") {
+                match AnswersFile::from_answers(rf.answers()).write(&path) {
+                    Ok(()) => {
+                        println!();
+                        println!(" Answers saved to `{}`", path);
+                    }
+                    Err(e) => eprintln!(
+                        "{} cannot save answers to `{}`: {}",
+                        "WARNING:".yellow(),
+                        path,
+                        e
+                    ),
+                }
+            }
             println!();
             println!("  ✨ project generated at `{}`", out_name);
             println!("{}", "     Ready to roll! 😎".green());
@@ -197,21 +629,175 @@ async fn main() {
             eprintln!("{}: {}", "ERROR".red(), e);
         }
     }
-    rl.save_history(&history_path).expect("cannot save history");
 }
 
-fn get_param_value(args: &Vec<String>, name: &str, short_name: &str ) -> Option<String> {
-    args
-        .iter()
+fn list_templates(root: &Path, location: &str) {
+    let templates = match source::find_templates(root) {
+        Ok(templates) => templates,
+        Err(e) => {
+            eprintln!("{}: cannot list templates. {}", "ERROR".red(), e);
+            return;
+        }
+    };
+    println!(" Templates in `{}`:", location);
+    println!();
+    for path in templates {
+        let name = core::read_config(root.join(&path).join("Reframe.toml"))
+            .map(|config| config.reframe.name)
+            .unwrap_or_default();
+        let path = format!("{}", path.display());
+        let path = if path.is_empty() { "." } else { &path };
+        println!(" * {0: <40} - {1: <10}", path, name);
+    }
+    println!();
+}
+
+fn cache_command(args: &[String], cache: &Cache) {
+    match args.get(2).map(|a| a.as_str()) {
+        Some("list") | None => match cache.list() {
+            Ok(entries) => {
+                println!(" Cached sources in `{}`:", cache.root().display());
+                println!();
+                for entry in entries {
+                    println!(
+                        " * {0: <50} {1: <10} fetched at {2}",
+                        entry.source,
+                        entry.reference.as_deref().unwrap_or("default"),
+                        entry.fetched_at
+                    );
+                }
+                println!();
+            }
+            Err(e) => eprintln!("{}: cannot list cache. {}", "ERROR".red(), e),
+        },
+        Some("clean") => match cache.clean() {
+            Ok(count) => println!(" {} cached source(s) removed.", count),
+            Err(e) => eprintln!("{}: cannot clean cache. {}", "ERROR".red(), e),
+        },
+        Some(cmd) => {
+            eprintln!("{}: unknown cache command `{}`", "ERROR".red(), cmd);
+            print_usage(args);
+        }
+    }
+}
+
+async fn search_command(args: &[String], cache: &Cache, registries: &[String]) {
+    let mut terms = vec![];
+    let mut tags = vec![];
+    let mut iter = args.iter().skip(2);
+    while let Some(arg) = iter.next() {
+        if arg == "--tag" || arg == "-t" {
+            tags.extend(iter.next().cloned());
+        } else if let Some(tag) = arg.strip_prefix("--tag=") {
+            tags.push(tag.to_string());
+        } else if !arg.starts_with('-') {
+            terms.push(arg.to_owned());
+        }
+    }
+    let refresh = args.contains(&"--refresh".to_string());
+
+    let entries = match registry::load_cached_sources(cache, registries, refresh).await {
+        Ok((entries, errors)) => {
+            for (location, e) in errors.iter() {
+                eprintln!(
+                    " {} cannot query registry `{}`: {}",
+                    "WARNING:".yellow(),
+                    location,
+                    e
+                );
+            }
+            entries
+        }
+        Err(e) => {
+            eprintln!("😭 {} {}", "FAILED:".red(), e);
+            eprintln!();
+            return;
+        }
+    };
+
+    let found = registry::search(&entries, &terms, &tags);
+    if found.is_empty() {
+        println!(" No source found for `{}`.", terms.join(" "));
+        println!();
+        return;
+    }
+    println!(" Found {} source(s):", found.len());
+    println!();
+    for entry in found {
+        print_registry_entry(entry, "*", registries.len() > 1);
+    }
+    println!();
+}
+
+/// Let the user pick a source from the registry index, cached if available,
+/// typing words filters the list. Returns `None` when aborted.
+async fn pick_source(cache: &Cache, registries: &[String]) -> io::Result<Option<String>> {
+    let (entries, errors) = registry::load_cached_sources(cache, registries, false).await?;
+    for (location, e) in errors.iter() {
+        eprintln!(
+            " {} cannot query registry `{}`: {}",
+            "WARNING:".yellow(),
+            location,
+            e
+        );
+    }
+
+    let mut rl = Editor::<()>::new().map_err(io::Error::other)?;
+    let mut found = registry::search::<&str>(&entries, &[], &[]);
+    loop {
+        if found.is_empty() {
+            println!(" No source found.");
+        } else {
+            println!(" Available sources:");
+        }
+        println!();
+        for (i, entry) in found.iter().enumerate() {
+            print_registry_entry(entry, &format!("{: >3})", i + 1), registries.len() > 1);
+        }
+        println!();
+
+        let input = match rl.readline(&format!(
+            "  ➢ {} : ",
+            "Pick a number, or type to filter (empty shows all)".bright_blue()
+        )) {
+            Ok(input) => input.trim().to_string(),
+            Err(_) => return Ok(None),
+        };
+        match input.parse::<usize>() {
+            Ok(n) if (1..=found.len()).contains(&n) => {
+                let spec = found[n - 1].source_spec();
+                println!();
+                println!(" Using `{}`", spec);
+                return Ok(Some(spec));
+            }
+            Ok(n) => println!("    No source number {}", n),
+            Err(_) => {
+                let terms: Vec<&str> = input.split_whitespace().collect();
+                found = registry::search(&entries, &terms, &[]);
+            }
+        }
+        println!();
+    }
+}
+
+/// All values of repeatable option, eg: `--registry=a --registry=b`.
+fn get_param_values(args: &[String], name: &str) -> Vec<String> {
+    let prefix = format!("{}=", name);
+    args.iter()
+        .filter_map(|a| a.trim().strip_prefix(&prefix))
+        .map(|a| a.to_string())
+        .collect()
+}
+
+fn get_param_value(args: &[String], name: &str, short_name: &str) -> Option<String> {
+    args.iter()
         .map(|a| a.trim())
-        .filter(|a| {
-            a.starts_with(name) || (short_name != "" && a.starts_with(short_name))
-        })
+        .filter(|a| a.starts_with(name) || (!short_name.is_empty() && a.starts_with(short_name)))
         .map(|a| {
             let s = a.split("=").collect::<Vec<&str>>();
             if s.len() == 2 {
                 Some(s[1].to_string())
-            }else{
+            } else {
                 None
             }
         })
@@ -0,0 +1,112 @@
+use log::debug;
+
+use crate::answers::AnswersFile;
+use crate::cache;
+use crate::core::Answer;
+
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Answers given last time per template, offered as the prompt defaults on next runs.
+/// Stored as answers file per template, secret params are never recorded.
+pub struct AnswerMemory {
+    root: PathBuf,
+}
+
+impl AnswerMemory {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        AnswerMemory {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Memory in the user data dir, eg: `~/.local/share/reframe/answers` on Linux,
+    /// the data dir can be overridden by `REFRAME_DATA_DIR` env var.
+    pub fn user_default() -> Self {
+        let root = env::var("REFRAME_DATA_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| {
+                dirs::data_dir()
+                    .unwrap_or_else(env::temp_dir)
+                    .join("reframe")
+            });
+        Self::new(root.join("answers"))
+    }
+
+    fn path(&self, template: &str) -> PathBuf {
+        self.root
+            .join(format!("{}.toml", cache::sanitize(template)))
+    }
+
+    /// Answers given last time to `template`, empty when never generated.
+    pub fn recall(&self, template: &str) -> HashMap<String, String> {
+        let path = self.path(template);
+        if !path.exists() {
+            return HashMap::new();
+        }
+        match AnswersFile::read(&path) {
+            Ok(answers) => answers
+                .params()
+                .into_iter()
+                .filter_map(|p| p.value.map(|v| (p.key, v)))
+                .collect(),
+            Err(e) => {
+                debug!("ignoring previous answers `{}`: {}", path.display(), e);
+                HashMap::new()
+            }
+        }
+    }
+
+    pub fn remember(&self, template: &str, answers: &[Answer]) -> io::Result<()> {
+        fs::create_dir_all(&self.root)?;
+        AnswersFile::from_answers(answers).write(self.path(template))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{AnswerSource, ParamKind};
+    use crate::util;
+
+    #[test]
+    fn test_remember_and_recall() {
+        let root = env::temp_dir().join(format!(
+            "reframe_test_memory_{}",
+            util::get_current_time_millis()
+        ));
+        let memory = AnswerMemory::new(&root);
+        let template = "github.com/anvie/basic-rust.rf";
+        assert!(memory.recall(template).is_empty());
+
+        let answer = |key: &str, value: &str, secret| Answer {
+            key: key.to_string(),
+            value: value.to_string(),
+            kind: ParamKind::String,
+            secret,
+            source: AnswerSource::Prompt,
+        };
+        memory
+            .remember(
+                template,
+                &[
+                    answer("author_name", "Robin", false),
+                    answer("token", "s3cr3t", true),
+                ],
+            )
+            .unwrap();
+
+        let recalled = memory.recall(template);
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled["author_name"], "Robin");
+        assert!(memory.recall("github.com/anvie/other.rf").is_empty());
+        assert!(!fs::read_to_string(memory.path(template))
+            .unwrap()
+            .contains("s3cr3t"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
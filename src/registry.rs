@@ -0,0 +1,544 @@
+use lazy_static::lazy_static;
+use log::debug;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{cache::Cache, util};
+
+use std::{collections::HashSet, fs, io, path::PathBuf};
+
+/// Default registry index.
+pub const REGISTRY_URL: &str =
+    "https://raw.githubusercontent.com/Ansvia/reframe/master/registry.toml";
+
+/// Markdown list of the old registry, used when the index is not available.
+pub const SOURCES_URL: &str = "https://raw.githubusercontent.com/Ansvia/reframe/master/SOURCES.md";
+
+lazy_static! {
+    static ref RE_MARKDOWN_ENTRY: Regex =
+        Regex::new(r"^\s*[*-] \[([^\]]+)\]\(([^)]+)\)\s*-\s*(.*)$").unwrap();
+    static ref RE_GITHUB_URL: Regex =
+        Regex::new(r"^https://github\.com/[\w.-]+/[\w.-]+?(\.rf)?/?$").unwrap();
+}
+
+/// Template listed in a registry.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RegistryEntry {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Source passed to reframe, eg: `anvie/basic-rust` or archive url.
+    pub source: String,
+    /// Branch or tag used when none is requested.
+    #[serde(default)]
+    pub default_ref: Option<String>,
+    /// Minimum Reframe version required by the template.
+    #[serde(default)]
+    pub min_version: Option<String>,
+    /// Registry the entry was listed in.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub registry: String,
+}
+
+impl RegistryEntry {
+    /// Whether the running Reframe satisfies `min_version`.
+    pub fn is_supported(&self) -> bool {
+        self.min_version.as_ref().is_none_or(|min_version| {
+            util::compare_version(min_version, env!("CARGO_PKG_VERSION")) >= 0
+        })
+    }
+
+    /// Source with the default ref applied, eg: `anvie/basic-rust@v1.0`.
+    pub fn source_spec(&self) -> String {
+        match self.default_ref.as_ref() {
+            Some(reference) => format!("{}@{}", self.source, reference),
+            None => self.source.to_owned(),
+        }
+    }
+}
+
+/// Registry index, in TOML:
+///
+/// ```toml
+/// [[template]]
+/// name = "anvie/basic-rust"
+/// description = "Basic rust project."
+/// tags = ["rust"]
+/// source = "anvie/basic-rust"
+/// default_ref = "master"
+/// min_version = "0.5.0"
+/// ```
+///
+/// or in JSON: `{"templates": [{"name": "anvie/basic-rust", ...}]}`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RegistryIndex {
+    #[serde(default, alias = "template")]
+    pub templates: Vec<RegistryEntry>,
+}
+
+impl RegistryIndex {
+    /// Parse index from JSON, TOML or the Markdown list of old registries,
+    /// `url` is used to pick the format by extension.
+    pub fn parse(text: &str, url: &str) -> io::Result<Self> {
+        let path = url.split('?').next().unwrap_or(url).to_lowercase();
+        let invalid = |e: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid registry index `{}`: {}", url, e),
+            )
+        };
+
+        if path.ends_with(".json") || (!path.ends_with(".md") && text.trim_start().starts_with('{'))
+        {
+            return serde_json::from_str(text).map_err(|e| invalid(e.to_string()));
+        }
+        if path.ends_with(".toml") {
+            return toml::from_str(text).map_err(|e| invalid(e.to_string()));
+        }
+        if !path.ends_with(".md") {
+            if let Ok(index) = toml::from_str::<RegistryIndex>(text) {
+                if !index.templates.is_empty() {
+                    return Ok(index);
+                }
+            }
+        }
+        Ok(Self::parse_markdown(text))
+    }
+
+    /// Entries of Markdown list, eg:
+    /// `* [anvie/basic-rust](https://github.com/anvie/basic-rust.rf) - Basic rust project.`
+    pub fn parse_markdown(text: &str) -> Self {
+        let templates = text
+            .lines()
+            .filter_map(|line| RE_MARKDOWN_ENTRY.captures(line))
+            .map(|caps| {
+                let name = caps[1].trim().to_string();
+                let url = caps[2].trim();
+                // github repos are referred by name, the `.rf` postfix is implied
+                let source = if RE_GITHUB_URL.is_match(url) && name.contains('/') {
+                    name.to_owned()
+                } else {
+                    url.to_string()
+                };
+                RegistryEntry {
+                    name,
+                    description: caps[3].trim().to_string(),
+                    tags: vec![],
+                    source,
+                    default_ref: None,
+                    min_version: None,
+                    registry: String::new(),
+                }
+            })
+            .collect();
+        RegistryIndex { templates }
+    }
+}
+
+/// Fetch and parse registry index at `location`, url or local file path.
+pub async fn fetch_index(location: &str) -> io::Result<RegistryIndex> {
+    debug!("querying registry: {}", location);
+    let text = if location.starts_with("http://") || location.starts_with("https://") {
+        util::get_text(location, &[]).await?
+    } else {
+        let path = location.strip_prefix("file://").unwrap_or(location);
+        fs::read_to_string(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("cannot read registry `{}`: {}", location, e),
+            )
+        })?
+    };
+    let mut index = RegistryIndex::parse(&text, location)?;
+    for entry in index.templates.iter_mut() {
+        entry.registry = location.to_string();
+    }
+    Ok(index)
+}
+
+/// Templates of the default registry, falls back to the Markdown list
+/// when the index is not available.
+pub async fn fetch_default_index() -> io::Result<RegistryIndex> {
+    match fetch_index(REGISTRY_URL).await {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            debug!("{}, falling back to {}", e, SOURCES_URL);
+            fetch_index(SOURCES_URL).await
+        }
+        rv => rv,
+    }
+}
+
+/// Templates of all `registries` in order, the default registry when empty.
+///
+/// Entries with the same source are listed once, from the first registry listing it.
+/// Registries that cannot be read are returned along with their errors.
+pub async fn get_available_sources(
+    registries: &[String],
+) -> (Vec<RegistryEntry>, Vec<(String, io::Error)>) {
+    let mut entries = vec![];
+    let mut errors = vec![];
+    let mut seen = HashSet::new();
+
+    let mut indexes = vec![];
+    if registries.is_empty() {
+        indexes.push((REGISTRY_URL.to_string(), fetch_default_index().await));
+    }
+    for location in registries {
+        indexes.push((location.to_owned(), fetch_index(location).await));
+    }
+
+    for (location, index) in indexes {
+        match index {
+            Ok(index) => entries.extend(
+                index
+                    .templates
+                    .into_iter()
+                    .filter(|entry| seen.insert(entry.source_spec())),
+            ),
+            Err(e) => errors.push((location, e)),
+        }
+    }
+    (entries, errors)
+}
+
+/// Merged entries of registries, cached for `reframe search`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CachedIndex {
+    pub registries: Vec<String>,
+    pub fetched_at: String,
+    pub templates: Vec<RegistryEntry>,
+}
+
+impl CachedIndex {
+    pub fn path(cache: &Cache) -> PathBuf {
+        cache.root().join("registry-index.json")
+    }
+
+    pub fn read(cache: &Cache) -> Option<Self> {
+        let data = fs::read(Self::path(cache)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn write(&self, cache: &Cache) -> io::Result<()> {
+        fs::create_dir_all(cache.root())?;
+        let data = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        fs::write(Self::path(cache), data)
+    }
+}
+
+/// Entries of `registries` from cache, fetched again when `refresh`,
+/// not cached yet or cached for other registries.
+///
+/// The cached copy is used when registries cannot be queried.
+pub async fn load_cached_sources(
+    cache: &Cache,
+    registries: &[String],
+    refresh: bool,
+) -> io::Result<(Vec<RegistryEntry>, Vec<(String, io::Error)>)> {
+    let cached = CachedIndex::read(cache).filter(|index| index.registries == registries);
+    if let (false, Some(index)) = (refresh, cached.as_ref()) {
+        debug!("using cached registry index from {}", index.fetched_at);
+        return Ok((index.templates.clone(), vec![]));
+    }
+
+    let (templates, errors) = get_available_sources(registries).await;
+    if templates.is_empty() && !errors.is_empty() {
+        return match cached {
+            Some(index) => Ok((index.templates, errors)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no registry available".to_string(),
+            )),
+        };
+    }
+
+    let index = CachedIndex {
+        registries: registries.to_vec(),
+        fetched_at: chrono::Utc::now().to_rfc3339(),
+        templates,
+    };
+    index.write(cache)?;
+    Ok((index.templates, errors))
+}
+
+/// Relevance of `entry` for a lowercase search `term`, 0 means no match.
+fn score(entry: &RegistryEntry, term: &str) -> u32 {
+    let name = entry.name.to_lowercase();
+    let short_name = name.rsplit('/').next().unwrap_or(&name);
+    let description = entry.description.to_lowercase();
+
+    if name == term || short_name == term {
+        100
+    } else if short_name.starts_with(term) {
+        60
+    } else if name.contains(term) {
+        50
+    } else if entry.tags.iter().any(|tag| tag.to_lowercase() == term) {
+        40
+    } else if description
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word == term)
+    {
+        30
+    } else if description.contains(term) {
+        20
+    } else if is_subsequence(term, short_name) {
+        10
+    } else {
+        0
+    }
+}
+
+/// Whether every char of `needle` appears in `haystack` in order, eg: `brs` in `basic-rust`.
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|c| chars.any(|h| h == c))
+}
+
+/// Entries matching all `terms` and having all `tags`, most relevant first.
+pub fn search<'a, S: AsRef<str>>(
+    entries: &'a [RegistryEntry],
+    terms: &[S],
+    tags: &[S],
+) -> Vec<&'a RegistryEntry> {
+    let terms: Vec<String> = terms.iter().map(|a| a.as_ref().to_lowercase()).collect();
+    let mut found: Vec<(u32, &RegistryEntry)> = entries
+        .iter()
+        .filter(|entry| {
+            tags.iter().all(|tag| {
+                entry
+                    .tags
+                    .iter()
+                    .any(|a| a.eq_ignore_ascii_case(tag.as_ref()))
+            })
+        })
+        .filter_map(|entry| {
+            let scores: Vec<u32> = terms.iter().map(|term| score(entry, term)).collect();
+            if scores.contains(&0) {
+                None
+            } else {
+                Some((scores.iter().sum(), entry))
+            }
+        })
+        .collect();
+    found.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(a.name.cmp(&b.name)));
+    found.into_iter().map(|(_, entry)| entry).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml_index() {
+        let index = RegistryIndex::parse(
+            r#"
+            [[template]]
+            name = "anvie/basic-rust"
+            description = "Basic rust project."
+            tags = ["rust", "cli"]
+            source = "anvie/basic-rust"
+            default_ref = "v1.0"
+            min_version = "0.5.0"
+
+            [[template]]
+            name = "future"
+            source = "https://example.com/future.tar.gz"
+            min_version = "99.0.0"
+            "#,
+            "https://example.com/registry.toml",
+        )
+        .unwrap();
+        assert_eq!(index.templates.len(), 2);
+        let basic = &index.templates[0];
+        assert_eq!(basic.tags, vec!["rust", "cli"]);
+        assert_eq!(basic.source_spec(), "anvie/basic-rust@v1.0");
+        assert!(basic.is_supported());
+        assert!(!index.templates[1].is_supported());
+        assert_eq!(index.templates[1].description, "");
+    }
+
+    #[tokio::test]
+    async fn test_merge_registries() {
+        use crate::test_util::serve;
+
+        let path = std::env::temp_dir().join(format!(
+            "reframe_test_registry_{}.toml",
+            util::get_current_time_millis()
+        ));
+        fs::write(
+            &path,
+            "[[template]]\nname = \"ours/service\"\nsource = \"gitea:git.example.com/ours/service\"\n\n\
+             [[template]]\nname = \"anvie/basic-rust\"\nsource = \"anvie/basic-rust\"\n",
+        )
+        .unwrap();
+        let base_url = serve(
+            br#"{"templates": [
+                {"name": "anvie/basic-rust", "source": "anvie/basic-rust"},
+                {"name": "anvie/rust-grpc", "source": "anvie/rust-grpc"}
+            ]}"#
+            .to_vec(),
+        );
+        let local = format!("{}", path.display());
+        let remote = format!("{}/index.json", base_url);
+        let missing = "/nonexistent/registry.toml".to_string();
+
+        let (entries, errors) =
+            get_available_sources(&[local.to_owned(), remote.to_owned(), missing.to_owned()]).await;
+        let listed: Vec<_> = entries
+            .iter()
+            .map(|a| (a.name.as_str(), a.registry.as_str()))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("ours/service", local.as_str()),
+                ("anvie/basic-rust", local.as_str()),
+                ("anvie/rust-grpc", remote.as_str()),
+            ]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, missing);
+        assert_eq!(errors[0].1.kind(), io::ErrorKind::NotFound);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    fn entry(name: &str, description: &str, tags: &[&str]) -> RegistryEntry {
+        RegistryEntry {
+            name: name.to_string(),
+            description: description.to_string(),
+            tags: tags.iter().map(|a| a.to_string()).collect(),
+            source: name.to_string(),
+            default_ref: None,
+            min_version: None,
+            registry: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let entries = vec![
+            entry(
+                "anvie/rust-grpc",
+                "Rust application with gRPC support.",
+                &["rust", "grpc"],
+            ),
+            entry("anvie/basic-rust", "Basic rust project.", &["rust"]),
+            entry("anvie/rust-rest", "Restful API server.", &["rust", "web"]),
+            entry("anvie/nemo2", "Next.js + Tailwind.", &["web"]),
+        ];
+        let names = |found: Vec<&RegistryEntry>| -> Vec<String> {
+            found.iter().map(|a| a.name.to_owned()).collect()
+        };
+
+        assert_eq!(
+            names(search(&entries, &["grpc"], &[])),
+            vec!["anvie/rust-grpc"]
+        );
+        // name prefix ranks above description match
+        assert_eq!(
+            names(search(&entries, &["rust"], &[])),
+            vec!["anvie/rust-grpc", "anvie/rust-rest", "anvie/basic-rust"]
+        );
+        assert_eq!(
+            names(search(&entries, &["Rust", "server"], &[])),
+            vec!["anvie/rust-rest"]
+        );
+        assert_eq!(
+            names(search(&entries, &[], &["web"])),
+            vec!["anvie/nemo2", "anvie/rust-rest"]
+        );
+        // fuzzy
+        assert_eq!(
+            names(search(&entries, &["bscrst"], &[])),
+            vec!["anvie/basic-rust"]
+        );
+        assert!(search(&entries, &["python"], &[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cached_sources() {
+        use crate::test_util::serve_with;
+        use crate::test_util::Response;
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base_url = serve_with(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Response::ok(br#"{"templates": [{"name": "x/y", "source": "x/y"}]}"#.to_vec())
+        });
+        let cache = Cache::new(std::env::temp_dir().join(format!(
+            "reframe_test_registry_cache_{}",
+            util::get_current_time_millis()
+        )));
+        let registries = vec![format!("{}/index.json", base_url)];
+
+        for refresh in [false, false, true] {
+            let (entries, errors) = load_cached_sources(&cache, &registries, refresh)
+                .await
+                .unwrap();
+            assert_eq!(entries[0].registry, registries[0]);
+            assert!(errors.is_empty());
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // other registries never use the cached copy
+        let err = load_cached_sources(&cache, &["/nonexistent.toml".to_string()], false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let _ = fs::remove_dir_all(cache.root());
+    }
+
+    #[test]
+    fn test_parse_bundled_registry() {
+        let index = RegistryIndex::parse(include_str!("../registry.toml"), REGISTRY_URL).unwrap();
+        assert!(!index.templates.is_empty());
+        assert!(index.templates.iter().all(|a| !a.tags.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_json_index() {
+        let index = RegistryIndex::parse(
+            r#"{"templates": [{"name": "x", "source": "gitlab:group/x", "tags": ["go"]}]}"#,
+            "https://example.com/index",
+        )
+        .unwrap();
+        assert_eq!(index.templates[0].source, "gitlab:group/x");
+        assert_eq!(index.templates[0].tags, vec!["go"]);
+
+        let err = RegistryIndex::parse("{", "https://example.com/index.json").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_parse_markdown_fallback() {
+        let index = RegistryIndex::parse(
+            "# List of reframe sources:\n\n\
+             * [anvie/basic-rust](https://github.com/anvie/basic-rust.rf) - Basic rust project.\n\
+             * [Ansvia/mainframe](https://github.com/ansvia/mainframe.rf) - Full-stack framework.\n\
+             - [tarball](https://example.com/t.tar.gz) - Template archive.\n\
+             **NOTES**: PRs are welcome.\n",
+            "https://example.com/SOURCES.md",
+        )
+        .unwrap();
+        let sources: Vec<_> = index.templates.iter().map(|a| a.source.as_str()).collect();
+        assert_eq!(
+            sources,
+            vec![
+                "anvie/basic-rust",
+                "Ansvia/mainframe",
+                "https://example.com/t.tar.gz"
+            ]
+        );
+        assert_eq!(index.templates[0].description, "Basic rust project.");
+    }
+}
@@ -0,0 +1,1790 @@
+use log::debug;
+
+use crate::{
+    archive,
+    cache::{Cache, CacheEntry, SOURCE_DIR_NAME},
+    git,
+    user_config::UserConfig,
+    util, version,
+};
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Provider of remote reframe sources distributed as archive.
+///
+/// Every provider knows how to build the archive url for the requested reference
+/// and where the source root is located after the archive extracted.
+pub trait SourceProvider {
+    /// Name of the provider, eg: `github`.
+    fn name(&self) -> &'static str;
+
+    /// Human readable location of the source, used in messages.
+    fn location(&self) -> String;
+
+    /// Host used to look up credentials in user config, eg: `github.com`.
+    fn host(&self) -> String;
+
+    /// Env var holding access token for this provider, eg: `GITHUB_TOKEN`.
+    fn token_env(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Header sending the access `token`.
+    fn auth_header(&self, token: &str) -> (&'static str, String) {
+        ("Authorization", format!("Bearer {}", token))
+    }
+
+    /// Url of the archive containing the source at `reference` (branch, tag or commit),
+    /// when `None` the default branch is used.
+    fn archive_url(&self, reference: Option<&str>) -> String;
+
+    /// Candidate archive urls tried in order until one is found.
+    fn archive_urls(&self, reference: Option<&str>) -> Vec<String> {
+        vec![self.archive_url(reference)]
+    }
+
+    /// Candidate archive urls used when access token is available,
+    /// private repositories may only be reachable through the provider API.
+    fn private_archive_urls(&self, reference: Option<&str>) -> Vec<String> {
+        self.archive_urls(reference)
+    }
+
+    /// Urls of the provider API listing the repository tags, tried in order until one is found,
+    /// empty when the provider has no tags.
+    fn tags_urls(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Find the source root dir inside `extracted_dir`,
+    /// by default the single top-level dir of the archive.
+    fn find_root(&self, extracted_dir: &Path, reference: Option<&str>) -> io::Result<PathBuf> {
+        let _ = reference;
+        single_root(extracted_dir)
+    }
+}
+
+type ProviderParser = fn(&str) -> Option<Box<dyn SourceProvider>>;
+
+/// Registered providers, checked in order, the first one recognizing the source wins.
+const PROVIDERS: &[ProviderParser] = &[
+    HttpArchive::parse,
+    GitLab::parse,
+    Gitea::parse,
+    Bitbucket::parse,
+    GitHub::parse,
+];
+
+/// Find provider for remote source, eg: `gitlab:group/repo`.
+pub fn provider_for(source: &str) -> Option<Box<dyn SourceProvider>> {
+    PROVIDERS.iter().find_map(|parse| parse(source))
+}
+
+/// Options used when fetching source.
+pub struct FetchOptions<'a> {
+    pub cache: &'a Cache,
+    /// Only use the cached copy, don't touch network.
+    pub offline: bool,
+    /// Credentials of private repositories.
+    pub user_config: &'a UserConfig,
+    /// Expected SHA-256 digest of the source archive.
+    pub sha256: Option<&'a str>,
+}
+
+/// Source fetched into cache.
+#[derive(Debug)]
+pub struct Fetched {
+    /// Source root dir.
+    pub root: PathBuf,
+    /// SHA-256 digest of the source archive, `None` for directories and git repositories.
+    pub sha256: Option<String>,
+}
+
+/// Location of a reframe source.
+pub enum Source {
+    /// Directory on local disk.
+    Local(PathBuf),
+    /// Plain git repository url.
+    Git(String),
+    /// Zip or tarball archive on local disk.
+    Archive(PathBuf),
+    /// Archive read from standard input, given as `-`.
+    Stdin,
+    /// Archive served by a source provider.
+    Remote(Box<dyn SourceProvider>),
+}
+
+impl Source {
+    pub fn parse(source: &str) -> io::Result<Self> {
+        if git::is_git_url(source) {
+            return Ok(Source::Git(source.to_string()));
+        }
+        if source == "-" {
+            return Ok(Source::Stdin);
+        }
+        let path = Path::new(source);
+        if path.is_file() {
+            return Ok(Source::Archive(path.to_path_buf()));
+        }
+        if path.exists() {
+            return Ok(Source::Local(path.to_path_buf()));
+        }
+        provider_for(source).map(Source::Remote).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unrecognized source `{}`", source),
+            )
+        })
+    }
+
+    /// Human readable location of the source, used in messages.
+    pub fn location(&self) -> String {
+        match self {
+            Source::Local(path) => format!("{}", path.display()),
+            Source::Git(url) => url.to_owned(),
+            Source::Archive(path) => format!("{}", path.display()),
+            Source::Stdin => "stdin".to_string(),
+            Source::Remote(provider) => provider.location(),
+        }
+    }
+
+    /// Names of the repository tags, listed through the provider API or `git ls-remote`,
+    /// in offline mode the cached references are listed instead.
+    pub async fn tags(&self, opts: &FetchOptions<'_>) -> io::Result<Vec<String>> {
+        let (provider, urls) = match self {
+            Source::Git(_) => ("git", vec![]),
+            Source::Remote(provider) => (provider.name(), provider.tags_urls()),
+            _ => ("", vec![]),
+        };
+        if provider.is_empty() || (provider != "git" && urls.is_empty()) {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{}` has no tags", self.location()),
+            ))?;
+        }
+
+        let location = match self {
+            Source::Git(url) => url.to_owned(),
+            _ => self.location(),
+        };
+        if opts.offline {
+            return Ok(opts
+                .cache
+                .list()?
+                .into_iter()
+                .filter(|entry| entry.provider == provider && entry.source == location)
+                .filter_map(|entry| entry.reference)
+                .collect());
+        }
+
+        let provider = match self {
+            Source::Remote(provider) => provider,
+            _ => return git::ls_remote_tags(&location),
+        };
+        let headers = opts
+            .user_config
+            .token_for(&provider.host(), provider.token_env())
+            .map(|token| vec![provider.auth_header(&token)])
+            .unwrap_or_default();
+        let mut not_found = vec![];
+        for url in urls {
+            let (text, next) = match util::get_page(&url, &headers).await {
+                Ok(page) => page,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    not_found.push(url);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            // tags are not listed in version order, every page is needed
+            let mut tags = vec![];
+            let mut page = Some((url, text, next));
+            let mut visited = HashSet::new();
+            while let Some((url, text, next)) = page.take() {
+                let json: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid tags from {}: {}", url, e),
+                    )
+                })?;
+                let names = tag_names(&json);
+                let next = next_tags_page(&url, next, &json, names.len());
+                tags.extend(names);
+                visited.insert(url);
+                if let Some(next) = next.filter(|next| !visited.contains(next)) {
+                    let (text, after) = util::get_page(&next, &headers).await?;
+                    page = Some((next, text, after));
+                }
+            }
+            return Ok(tags);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "cannot list tags of `{}`, tried: {}",
+                location,
+                not_found.join(", ")
+            ),
+        ))
+    }
+
+    /// Resolve semver `requirement`, eg: `^1.2`, to the highest matching tag.
+    pub async fn resolve_version(
+        &self,
+        requirement: &str,
+        opts: &FetchOptions<'_>,
+    ) -> io::Result<String> {
+        let tags = self.tags(opts).await.map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("cannot resolve version `{}`: {}", requirement, e),
+            )
+        })?;
+        version::select_tag(&tags, requirement)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no tag of `{}` matches `{}`{}",
+                    self.location(),
+                    requirement,
+                    if tags.is_empty() {
+                        "".to_string()
+                    } else {
+                        format!(", available: {}", tags.join(", "))
+                    }
+                ),
+            )
+        })
+    }
+
+    /// Get the source into cache if needed and return the source root dir.
+    pub async fn fetch(
+        &self,
+        reference: Option<&str>,
+        opts: &FetchOptions<'_>,
+    ) -> io::Result<Fetched> {
+        let (cache, offline, pin) = (opts.cache, opts.offline, opts.sha256);
+        match self {
+            Source::Local(_) | Source::Git(_) if pin.is_some() => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot verify checksum of `{}`, only archive sources can be pinned",
+                    self.location()
+                ),
+            )),
+            Source::Local(path) => Ok(Fetched {
+                root: path.to_owned(),
+                sha256: None,
+            }),
+            Source::Archive(path) => {
+                let location = format!("{}", path.canonicalize()?.display());
+                let entry_dir = cache.entry_dir("archive", &location, None);
+                extract_local_archive(path, &entry_dir, &location, opts)
+            }
+            Source::Stdin => {
+                let entry_dir = cache.entry_dir("archive", "stdin", None);
+                fs::create_dir_all(&entry_dir)?;
+                let archive_path = entry_dir.join("stdin.archive");
+                let mut fout = fs::File::create(&archive_path)?;
+                io::copy(&mut io::stdin().lock(), &mut fout)?;
+                extract_local_archive(&archive_path, &entry_dir, "stdin", opts)
+            }
+            Source::Git(url) => {
+                let entry_dir = cache.entry_dir("git", url, reference);
+                let src_dir = entry_dir.join(SOURCE_DIR_NAME);
+                let cached = Cache::read_entry(&entry_dir).filter(|_| src_dir.exists());
+
+                if offline {
+                    return cached
+                        .map(|_| Fetched {
+                            root: src_dir,
+                            sha256: None,
+                        })
+                        .ok_or_else(|| not_cached(url));
+                }
+
+                let new_dir = entry_dir.join(format!("{}.new", SOURCE_DIR_NAME));
+                if let Err(e) = git::clone(url, reference, &new_dir) {
+                    let _ = fs::remove_dir_all(&new_dir);
+                    return match cached {
+                        Some(_) => {
+                            eprintln!(" Cannot clone ({}), using cached copy of `{}`", e, url);
+                            Ok(Fetched {
+                                root: src_dir,
+                                sha256: None,
+                            })
+                        }
+                        None => Err(e),
+                    };
+                }
+                if src_dir.exists() {
+                    fs::remove_dir_all(&src_dir)?;
+                }
+                fs::rename(&new_dir, &src_dir)?;
+                Cache::write_entry(&entry_dir, &CacheEntry::new("git", url, reference))?;
+
+                Ok(Fetched {
+                    root: src_dir,
+                    sha256: None,
+                })
+            }
+            Source::Remote(provider) => {
+                let location = provider.location();
+                let entry_dir = cache.entry_dir(provider.name(), &location, reference);
+                let src_dir = entry_dir.join(SOURCE_DIR_NAME);
+                let cached = Cache::read_entry(&entry_dir).filter(|_| src_dir.exists());
+
+                // cached copy not matching the pinned digest is never used
+                let cached = match (cached, pin) {
+                    (Some(entry), Some(pin)) if !entry.sha256_matches(pin) => {
+                        if offline {
+                            return Err(util::checksum_mismatch(
+                                &location,
+                                pin,
+                                entry.sha256.as_deref().unwrap_or("unknown"),
+                            ));
+                        }
+                        None
+                    }
+                    (cached, _) => cached,
+                };
+
+                if offline {
+                    let entry = cached.ok_or_else(|| not_cached(&location))?;
+                    return Ok(Fetched {
+                        root: provider.find_root(&src_dir, reference)?,
+                        sha256: entry.sha256,
+                    });
+                }
+
+                let token = opts
+                    .user_config
+                    .token_for(&provider.host(), provider.token_env());
+                let (urls, headers) = match token.as_ref() {
+                    Some(token) => (
+                        provider.private_archive_urls(reference),
+                        vec![provider.auth_header(token)],
+                    ),
+                    None => (provider.archive_urls(reference), vec![]),
+                };
+
+                let mut not_found = vec![];
+                let mut downloaded = None;
+                for url in urls {
+                    // validators only apply to the url they were received from
+                    let validators = cached
+                        .as_ref()
+                        .filter(|entry| entry.url.as_deref() == Some(url.as_str()))
+                        .map(|entry| entry.validators())
+                        .unwrap_or_default();
+                    let archive_path = entry_dir.join(archive_file_name(&url));
+                    match util::download(&url, &archive_path, &validators, &headers, pin).await {
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => not_found.push(url),
+                        rv => {
+                            downloaded = Some((url, archive_path, rv));
+                            break;
+                        }
+                    }
+                }
+
+                let sha256 = match downloaded {
+                    None => Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!(
+                            "cannot find `{}` at `{}`, tried: {}{}",
+                            location,
+                            reference.unwrap_or("default branch"),
+                            not_found.join(", "),
+                            if token.is_none() {
+                                private_hint(provider.as_ref())
+                            } else {
+                                "".to_string()
+                            }
+                        ),
+                    ))?,
+                    Some((url, archive_path, Ok(Some(downloaded)))) => {
+                        if src_dir.exists() {
+                            fs::remove_dir_all(&src_dir)?;
+                        }
+                        let limits = &opts.user_config.extract;
+                        if let Err(e) = archive::extract(&archive_path, &src_dir, limits) {
+                            // never leave partially extracted source in cache
+                            let _ = fs::remove_dir_all(&src_dir);
+                            return Err(e);
+                        }
+
+                        let mut entry = CacheEntry::new(provider.name(), &location, reference);
+                        entry.url = Some(url);
+                        entry.etag = downloaded.validators.etag;
+                        entry.last_modified = downloaded.validators.last_modified;
+                        entry.sha256 = Some(downloaded.sha256.to_owned());
+                        Cache::write_entry(&entry_dir, &entry)?;
+                        Some(downloaded.sha256)
+                    }
+                    Some((_, archive_path, Ok(None))) => {
+                        debug!("using cached copy of {}", location);
+                        cached_sha256(cached.as_ref(), &archive_path)
+                    }
+                    Some((_, archive_path, Err(e))) => {
+                        if cached.is_none() {
+                            return Err(e);
+                        }
+                        eprintln!(
+                            " Cannot download ({}), using cached copy of `{}`",
+                            e, location
+                        );
+                        cached_sha256(cached.as_ref(), &archive_path)
+                    }
+                };
+
+                let root = provider.find_root(&src_dir, reference)?;
+                debug!("source root: {}", root.display());
+                Ok(Fetched { root, sha256 })
+            }
+        }
+    }
+}
+
+/// Source with the requested reference and sub-directory,
+/// eg: `owner/repo@v1.2.0` for branch or tag, `owner/repo#1a2b3c4` for commit,
+/// `owner/repo//templates/rust-service` for template inside monorepo.
+pub struct SourceSpec {
+    pub source: Source,
+    pub reference: Option<String>,
+    pub subdir: Option<String>,
+}
+
+impl SourceSpec {
+    pub fn parse(spec: &str) -> io::Result<Self> {
+        if spec == "-" || Path::new(spec).exists() {
+            return Ok(SourceSpec {
+                source: Source::parse(spec)?,
+                reference: None,
+                subdir: None,
+            });
+        }
+        let (spec, subdir) = split_subdir(spec);
+        let (source, reference) = split_reference(spec)?;
+        Ok(SourceSpec {
+            source: Source::parse(source)?,
+            reference,
+            subdir,
+        })
+    }
+}
+
+/// Split `source//sub/dir` into source and sub-directory.
+fn split_subdir(spec: &str) -> (&str, Option<String>) {
+    let start = reference_search_start(spec);
+    match spec[start..].find("//") {
+        Some(pos) => {
+            let subdir = spec[start + pos + 2..].trim_matches('/');
+            let subdir = if subdir.is_empty() {
+                None
+            } else {
+                Some(subdir.to_string())
+            };
+            (&spec[..start + pos], subdir)
+        }
+        None => (spec, None),
+    }
+}
+
+/// Find every dir containing `Reframe.toml` inside `root`,
+/// returned as paths relative to `root` in sorted order.
+pub fn find_templates<P: AsRef<Path>>(root: P) -> io::Result<Vec<PathBuf>> {
+    fn walk(root: &Path, dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+        if dir.join("Reframe.toml").is_file() {
+            found.push(util::path_to_relative(dir, root));
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .map(|name| name.to_string_lossy().starts_with('.'))
+                == Some(true);
+            if path.is_dir() && !hidden {
+                walk(root, &path, found)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut found = vec![];
+    walk(root.as_ref(), root.as_ref(), &mut found)?;
+    found.sort();
+    Ok(found)
+}
+
+/// Point source `root` to its `subdir`, the sub-directory must contain `Reframe.toml`.
+pub fn select_subdir<P: AsRef<Path>>(root: P, subdir: Option<&str>) -> io::Result<PathBuf> {
+    let root = root.as_ref();
+    let subdir = match subdir {
+        Some(subdir) => subdir,
+        None => return Ok(root.to_path_buf()),
+    };
+
+    if Path::new(subdir)
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid sub-directory `{}`", subdir),
+        ))?;
+    }
+
+    let path = root.join(subdir);
+    if path.join("Reframe.toml").is_file() {
+        return Ok(path);
+    }
+
+    let available = find_templates(root)?
+        .iter()
+        .map(|p| format!("{}", p.display()))
+        .collect::<Vec<String>>();
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        if available.is_empty() {
+            format!("no `Reframe.toml` in sub-directory `{}`", subdir)
+        } else {
+            format!(
+                "no `Reframe.toml` in sub-directory `{}`, available: {}",
+                subdir,
+                available.join(", ")
+            )
+        },
+    ))
+}
+
+/// Position where reference marker is searched from,
+/// skipping user info of urls like `ssh://git@host/..` and `git@host:..`.
+fn reference_search_start(spec: &str) -> usize {
+    if let Some(i) = spec.find("://") {
+        let start = i + 3;
+        return spec[start..]
+            .find('/')
+            .map(|j| start + j)
+            .unwrap_or(spec.len());
+    }
+    if spec.starts_with("git@") {
+        return spec.find(':').unwrap_or(spec.len());
+    }
+    0
+}
+
+fn is_commit_sha(s: &str) -> bool {
+    (7..=40).contains(&s.len()) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn split_reference(spec: &str) -> io::Result<(&str, Option<String>)> {
+    let start = reference_search_start(spec);
+    let pos = match spec[start..].find(['@', '#']) {
+        Some(pos) => start + pos,
+        None => return Ok((spec, None)),
+    };
+
+    let (source, reference) = (&spec[..pos], &spec[pos + 1..]);
+    if reference.is_empty() {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("missing reference in `{}`", spec),
+        ))?;
+    }
+    if spec[pos..].starts_with('#') && !is_commit_sha(reference) {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("`{}` is not a valid commit sha", reference),
+        ))?;
+    }
+    Ok((source, Some(reference.to_string())))
+}
+
+/// Tag names from the provider API response, Bitbucket pages them in `values`.
+/// Url of the page after `url`: the next link of the response, the Bitbucket `next` field,
+/// or the following page number while the pages are full for APIs paged by `limit` (Gitea).
+fn next_tags_page(
+    url: &str,
+    next: Option<String>,
+    json: &serde_json::Value,
+    count: usize,
+) -> Option<String> {
+    let param = |key: &str| {
+        url.split_once('?')?
+            .1
+            .split('&')
+            .find_map(|p| p.strip_prefix(key)?.strip_prefix('='))
+            .and_then(|v| v.parse::<usize>().ok())
+    };
+    next.or_else(|| json.get("next")?.as_str().map(|a| a.to_string()))
+        .or_else(|| {
+            let limit = param("limit")?;
+            (count > 0 && count >= limit).then(|| {
+                let page = param("page").unwrap_or(1);
+                util::with_query_param(url, "page", &(page + 1).to_string())
+            })
+        })
+}
+
+fn tag_names(json: &serde_json::Value) -> Vec<String> {
+    json.get("values")
+        .unwrap_or(json)
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|tag| tag.get("name")?.as_str().map(|a| a.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn private_hint(provider: &dyn SourceProvider) -> String {
+    let env_hint = provider
+        .token_env()
+        .map(|name| format!("set `{}` or ", name))
+        .unwrap_or_default();
+    format!(
+        ". If the repository is private, {}add token for `{}` in the `[credentials]` section of user config",
+        env_hint,
+        provider.host()
+    )
+}
+
+fn not_cached(location: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("`{}` is not cached, cannot fetch in offline mode", location),
+    )
+}
+
+/// Digest of the cached archive, computed when the cache entry predates checksums.
+fn cached_sha256(entry: Option<&CacheEntry>, archive_path: &Path) -> Option<String> {
+    entry
+        .and_then(|entry| entry.sha256.to_owned())
+        .or_else(|| util::sha256_file(archive_path).ok())
+}
+
+/// Extract archive on local disk into cache entry, always replacing the previous copy.
+fn extract_local_archive(
+    archive_path: &Path,
+    entry_dir: &Path,
+    location: &str,
+    opts: &FetchOptions<'_>,
+) -> io::Result<Fetched> {
+    let sha256 = util::sha256_file(archive_path)?;
+    if let Some(pin) = opts.sha256 {
+        if !pin.eq_ignore_ascii_case(&sha256) {
+            return Err(util::checksum_mismatch(location, pin, &sha256));
+        }
+    }
+
+    let src_dir = entry_dir.join(SOURCE_DIR_NAME);
+    if src_dir.exists() {
+        fs::remove_dir_all(&src_dir)?;
+    }
+    if let Err(e) = archive::extract(archive_path, &src_dir, &opts.user_config.extract) {
+        let _ = fs::remove_dir_all(&src_dir);
+        return Err(e);
+    }
+    let mut entry = CacheEntry::new("archive", location, None);
+    entry.sha256 = Some(sha256.to_owned());
+    Cache::write_entry(entry_dir, &entry)?;
+    Ok(Fetched {
+        root: single_root(&src_dir)?,
+        sha256: Some(sha256),
+    })
+}
+
+fn archive_file_name(url: &str) -> &'static str {
+    match archive::Format::from_name(url) {
+        Some(archive::Format::TarGz) => "source.tar.gz",
+        Some(archive::Format::Tar) => "source.tar",
+        _ => "source.zip",
+    }
+}
+
+fn sub_dirs(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut dirs = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Source root of archive that contains `Reframe.toml` at the top
+/// or within the single top-level directory.
+fn single_root(extracted_dir: &Path) -> io::Result<PathBuf> {
+    if extracted_dir.join("Reframe.toml").exists() {
+        return Ok(extracted_dir.to_path_buf());
+    }
+    match sub_dirs(extracted_dir)?.as_slice() {
+        [dir] => Ok(dir.to_owned()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("cannot find source root in `{}`", extracted_dir.display()),
+        )),
+    }
+}
+
+/// Find top-level dir starting with `prefix`, used for providers that
+/// append the commit sha to the archive dir name.
+fn prefixed_root(extracted_dir: &Path, prefix: &str) -> io::Result<PathBuf> {
+    sub_dirs(extracted_dir)?
+        .into_iter()
+        .find(|dir| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().starts_with(prefix))
+                == Some(true)
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("cannot find `{}*` in `{}`", prefix, extracted_dir.display()),
+            )
+        })
+}
+
+fn split_owner_repo(path: &str) -> Option<(String, String)> {
+    let s: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match s.as_slice() {
+        [owner, repo] if !owner.is_empty() && !repo.is_empty() => {
+            Some((owner.to_string(), repo.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// GitHub repository, eg: `anvie/basic-rust` or `github:anvie/basic-rust`,
+/// the repository name is postfixed with `.rf`, falls back to the name without it.
+pub struct GitHub {
+    pub owner: String,
+    pub repo: String,
+}
+
+impl GitHub {
+    fn parse(source: &str) -> Option<Box<dyn SourceProvider>> {
+        let path = source.strip_prefix("github:").unwrap_or(source);
+        if path.contains(':') {
+            return None;
+        }
+        let (owner, repo) = split_owner_repo(path)?;
+        let repo = repo.trim_end_matches(".rf").to_string();
+        Some(Box::new(GitHub { owner, repo }))
+    }
+}
+
+impl SourceProvider for GitHub {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn location(&self) -> String {
+        format!("github.com/{}/{}.rf", self.owner, self.repo)
+    }
+
+    fn host(&self) -> String {
+        "github.com".to_string()
+    }
+
+    fn token_env(&self) -> Option<&'static str> {
+        Some("GITHUB_TOKEN")
+    }
+
+    fn archive_url(&self, reference: Option<&str>) -> String {
+        format!(
+            "https://github.com/{}/{}.rf/archive/{}.zip",
+            self.owner,
+            self.repo,
+            reference.unwrap_or("HEAD")
+        )
+    }
+
+    fn archive_urls(&self, reference: Option<&str>) -> Vec<String> {
+        vec![
+            self.archive_url(reference),
+            format!(
+                "https://github.com/{}/{}/archive/{}.zip",
+                self.owner,
+                self.repo,
+                reference.unwrap_or("HEAD")
+            ),
+        ]
+    }
+
+    fn tags_urls(&self) -> Vec<String> {
+        vec![
+            format!(
+                "https://api.github.com/repos/{}/{}.rf/tags?per_page=100",
+                self.owner, self.repo
+            ),
+            format!(
+                "https://api.github.com/repos/{}/{}/tags?per_page=100",
+                self.owner, self.repo
+            ),
+        ]
+    }
+
+    fn private_archive_urls(&self, reference: Option<&str>) -> Vec<String> {
+        let reference = reference.map(|r| format!("/{}", r)).unwrap_or_default();
+        vec![
+            format!(
+                "https://api.github.com/repos/{}/{}.rf/zipball{}",
+                self.owner, self.repo, reference
+            ),
+            format!(
+                "https://api.github.com/repos/{}/{}/zipball{}",
+                self.owner, self.repo, reference
+            ),
+        ]
+    }
+}
+
+/// GitLab repository, eg: `gitlab:group/repo` for gitlab.com
+/// or `gitlab:git.example.com/group/subgroup/repo` for self-hosted instance.
+pub struct GitLab {
+    pub host: String,
+    pub path: String,
+}
+
+impl GitLab {
+    fn parse(source: &str) -> Option<Box<dyn SourceProvider>> {
+        let path = source.strip_prefix("gitlab:")?.trim_matches('/');
+        let (host, path) = match path.split_once('/') {
+            Some((host, rest)) if host.contains('.') => (host, rest),
+            _ => ("gitlab.com", path),
+        };
+        if !path.contains('/') {
+            return None;
+        }
+        Some(Box::new(GitLab {
+            host: host.to_string(),
+            path: path.to_string(),
+        }))
+    }
+
+    fn repo(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+impl SourceProvider for GitLab {
+    fn name(&self) -> &'static str {
+        "gitlab"
+    }
+
+    fn location(&self) -> String {
+        format!("{}/{}", self.host, self.path)
+    }
+
+    fn host(&self) -> String {
+        self.host.to_owned()
+    }
+
+    fn token_env(&self) -> Option<&'static str> {
+        Some("GITLAB_TOKEN")
+    }
+
+    fn auth_header(&self, token: &str) -> (&'static str, String) {
+        ("PRIVATE-TOKEN", token.to_string())
+    }
+
+    fn archive_url(&self, reference: Option<&str>) -> String {
+        let reference = reference.unwrap_or("HEAD");
+        format!(
+            "https://{}/{}/-/archive/{}/{}-{}.zip",
+            self.host,
+            self.path,
+            reference,
+            self.repo(),
+            reference
+        )
+    }
+
+    fn private_archive_urls(&self, reference: Option<&str>) -> Vec<String> {
+        vec![format!(
+            "https://{}/api/v4/projects/{}/repository/archive.zip{}",
+            self.host,
+            self.path.replace('/', "%2F"),
+            reference.map(|r| format!("?sha={}", r)).unwrap_or_default()
+        )]
+    }
+
+    fn tags_urls(&self) -> Vec<String> {
+        vec![format!(
+            "https://{}/api/v4/projects/{}/repository/tags?per_page=100",
+            self.host,
+            self.path.replace('/', "%2F")
+        )]
+    }
+
+    fn find_root(&self, extracted_dir: &Path, _reference: Option<&str>) -> io::Result<PathBuf> {
+        // GitLab names the dir `{repo}-{ref}-{sha}`
+        prefixed_root(extracted_dir, &format!("{}-", self.repo()))
+    }
+}
+
+/// Gitea repository, eg: `gitea:git.example.com/owner/repo`.
+pub struct Gitea {
+    pub host: String,
+    pub owner: String,
+    pub repo: String,
+}
+
+impl Gitea {
+    fn parse(source: &str) -> Option<Box<dyn SourceProvider>> {
+        let path = source.strip_prefix("gitea:")?;
+        let (host, path) = path.trim_matches('/').split_once('/')?;
+        let (owner, repo) = split_owner_repo(path)?;
+        Some(Box::new(Gitea {
+            host: host.to_string(),
+            owner,
+            repo,
+        }))
+    }
+}
+
+impl SourceProvider for Gitea {
+    fn name(&self) -> &'static str {
+        "gitea"
+    }
+
+    fn location(&self) -> String {
+        format!("{}/{}/{}", self.host, self.owner, self.repo)
+    }
+
+    fn host(&self) -> String {
+        self.host.to_owned()
+    }
+
+    fn auth_header(&self, token: &str) -> (&'static str, String) {
+        ("Authorization", format!("token {}", token))
+    }
+
+    fn archive_url(&self, reference: Option<&str>) -> String {
+        format!(
+            "https://{}/{}/{}/archive/{}.zip",
+            self.host,
+            self.owner,
+            self.repo,
+            reference.unwrap_or("master")
+        )
+    }
+
+    fn tags_urls(&self) -> Vec<String> {
+        vec![format!(
+            "https://{}/api/v1/repos/{}/{}/tags?limit=50&page=1",
+            self.host, self.owner, self.repo
+        )]
+    }
+
+    fn find_root(&self, extracted_dir: &Path, _reference: Option<&str>) -> io::Result<PathBuf> {
+        // Gitea names the dir after the repository only
+        let path = extracted_dir.join(&self.repo);
+        if path.exists() {
+            Ok(path)
+        } else {
+            single_root(extracted_dir)
+        }
+    }
+}
+
+/// Bitbucket repository, eg: `bitbucket:owner/repo`.
+pub struct Bitbucket {
+    pub owner: String,
+    pub repo: String,
+}
+
+impl Bitbucket {
+    fn parse(source: &str) -> Option<Box<dyn SourceProvider>> {
+        let (owner, repo) = split_owner_repo(source.strip_prefix("bitbucket:")?)?;
+        Some(Box::new(Bitbucket { owner, repo }))
+    }
+}
+
+impl SourceProvider for Bitbucket {
+    fn name(&self) -> &'static str {
+        "bitbucket"
+    }
+
+    fn location(&self) -> String {
+        format!("bitbucket.org/{}/{}", self.owner, self.repo)
+    }
+
+    fn host(&self) -> String {
+        "bitbucket.org".to_string()
+    }
+
+    fn archive_url(&self, reference: Option<&str>) -> String {
+        format!(
+            "https://bitbucket.org/{}/{}/get/{}.zip",
+            self.owner,
+            self.repo,
+            reference.unwrap_or("HEAD")
+        )
+    }
+
+    fn tags_urls(&self) -> Vec<String> {
+        vec![format!(
+            "https://api.bitbucket.org/2.0/repositories/{}/{}/refs/tags?pagelen=100",
+            self.owner, self.repo
+        )]
+    }
+
+    fn find_root(&self, extracted_dir: &Path, _reference: Option<&str>) -> io::Result<PathBuf> {
+        // Bitbucket names the dir `{owner}-{repo}-{sha}`
+        prefixed_root(extracted_dir, &format!("{}-{}-", self.owner, self.repo))
+    }
+}
+
+/// Plain archive url, eg: `https://example.com/template.tar.gz`.
+pub struct HttpArchive {
+    pub url: String,
+}
+
+impl HttpArchive {
+    fn parse(source: &str) -> Option<Box<dyn SourceProvider>> {
+        if !source.starts_with("http://") && !source.starts_with("https://") {
+            return None;
+        }
+        let path = source.split('?').next().unwrap_or(source);
+        if archive::Format::from_name(path).is_some() {
+            Some(Box::new(HttpArchive {
+                url: source.to_string(),
+            }))
+        } else {
+            None
+        }
+    }
+}
+
+impl SourceProvider for HttpArchive {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn location(&self) -> String {
+        self.url.to_owned()
+    }
+
+    fn host(&self) -> String {
+        reqwest::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_default()
+    }
+
+    fn archive_url(&self, _reference: Option<&str>) -> String {
+        self.url.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{make_tar_gz, make_zip, serve, serve_with, Response};
+    use std::{
+        env,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    lazy_static::lazy_static! {
+        static ref NO_CREDENTIALS: UserConfig = UserConfig::default();
+    }
+
+    fn opts(cache: &Cache, offline: bool) -> FetchOptions<'_> {
+        FetchOptions {
+            cache,
+            offline,
+            user_config: &NO_CREDENTIALS,
+            sha256: None,
+        }
+    }
+
+    fn temp_cache(name: &str) -> Cache {
+        Cache::new(env::temp_dir().join(format!(
+            "reframe_test_{}_{}",
+            name,
+            util::get_current_time_millis()
+        )))
+    }
+
+    #[test]
+    fn test_provider_archive_urls() {
+        let p = provider_for("gitlab:group/sub/repo").unwrap();
+        assert_eq!(p.name(), "gitlab");
+        assert_eq!(
+            p.archive_url(Some("main")),
+            "https://gitlab.com/group/sub/repo/-/archive/main/repo-main.zip"
+        );
+
+        let p = provider_for("gitlab:git.example.com/group/repo").unwrap();
+        assert_eq!(p.location(), "git.example.com/group/repo");
+
+        let p = provider_for("gitea:git.example.com/owner/repo").unwrap();
+        assert_eq!(p.name(), "gitea");
+        assert_eq!(
+            p.archive_url(Some("v1.0")),
+            "https://git.example.com/owner/repo/archive/v1.0.zip"
+        );
+
+        let p = provider_for("bitbucket:owner/repo").unwrap();
+        assert_eq!(
+            p.archive_url(None),
+            "https://bitbucket.org/owner/repo/get/HEAD.zip"
+        );
+
+        let p = provider_for("anvie/basic-rust").unwrap();
+        assert_eq!(p.name(), "github");
+        assert_eq!(
+            p.archive_urls(Some("develop")),
+            vec![
+                "https://github.com/anvie/basic-rust.rf/archive/develop.zip",
+                "https://github.com/anvie/basic-rust/archive/develop.zip"
+            ]
+        );
+        assert_eq!(
+            provider_for("anvie/basic-rust.rf").unwrap().location(),
+            "github.com/anvie/basic-rust.rf"
+        );
+
+        let p = provider_for("https://example.com/t/template.tar.gz").unwrap();
+        assert_eq!(p.name(), "http");
+
+        assert!(provider_for("gitlab:repo").is_none());
+        assert!(provider_for("https://example.com/template").is_none());
+        assert!(provider_for("not-a-source").is_none());
+    }
+
+    #[test]
+    fn test_split_reference() {
+        assert_eq!(split_reference("anvie/x").unwrap(), ("anvie/x", None));
+        assert_eq!(
+            split_reference("anvie/x@v1.2.0").unwrap(),
+            ("anvie/x", Some("v1.2.0".to_string()))
+        );
+        assert_eq!(
+            split_reference("anvie/x@release/1.0").unwrap(),
+            ("anvie/x", Some("release/1.0".to_string()))
+        );
+        assert_eq!(
+            split_reference("anvie/x#1a2b3c4").unwrap(),
+            ("anvie/x", Some("1a2b3c4".to_string()))
+        );
+        assert_eq!(
+            split_reference("git@github.com:anvie/x.git").unwrap(),
+            ("git@github.com:anvie/x.git", None)
+        );
+        assert_eq!(
+            split_reference("ssh://git@host/x.git@develop").unwrap(),
+            ("ssh://git@host/x.git", Some("develop".to_string()))
+        );
+        assert!(split_reference("anvie/x#not-a-sha").is_err());
+        assert!(split_reference("anvie/x@").is_err());
+    }
+
+    #[test]
+    fn test_split_subdir() {
+        assert_eq!(split_subdir("anvie/x"), ("anvie/x", None));
+        assert_eq!(
+            split_subdir("anvie/x@v1//templates/rust-service/"),
+            ("anvie/x@v1", Some("templates/rust-service".to_string()))
+        );
+        assert_eq!(
+            split_subdir("https://host/t.tar.gz//a"),
+            ("https://host/t.tar.gz", Some("a".to_string()))
+        );
+        assert_eq!(
+            split_subdir("https://host/t.tar.gz"),
+            ("https://host/t.tar.gz", None)
+        );
+
+        let spec = SourceSpec::parse("gitlab:group/repo@main//templates/a").unwrap();
+        assert_eq!(spec.reference.as_deref(), Some("main"));
+        assert_eq!(spec.subdir.as_deref(), Some("templates/a"));
+        assert_eq!(spec.source.location(), "gitlab.com/group/repo");
+    }
+
+    #[test]
+    fn test_find_and_select_templates() {
+        let root = env::temp_dir().join(format!(
+            "reframe_test_monorepo_{}",
+            util::get_current_time_millis()
+        ));
+        for dir in ["templates/rust-service", "templates/web", ".git/x", "docs"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for dir in ["templates/rust-service", "templates/web", ".git/x"] {
+            fs::write(root.join(dir).join("Reframe.toml"), "").unwrap();
+        }
+
+        assert_eq!(
+            find_templates(&root).unwrap(),
+            vec![
+                PathBuf::from("templates/rust-service"),
+                PathBuf::from("templates/web")
+            ]
+        );
+
+        assert_eq!(select_subdir(&root, None).unwrap(), root);
+        assert_eq!(
+            select_subdir(&root, Some("templates/web")).unwrap(),
+            root.join("templates/web")
+        );
+        let err = select_subdir(&root, Some("docs")).unwrap_err();
+        assert!(err.to_string().contains("templates/rust-service"));
+        assert!(select_subdir(&root, Some("../x")).is_err());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_single_root() {
+        let dir = env::temp_dir().join(format!(
+            "reframe_test_single_root_{}",
+            util::get_current_time_millis()
+        ));
+        // GitHub strips the `v` of tag in dir name
+        fs::create_dir_all(dir.join("basic-rust.rf-1.2.0")).unwrap();
+        fs::write(dir.join("source.zip"), "").unwrap();
+        let p = provider_for("anvie/basic-rust").unwrap();
+        assert_eq!(
+            p.find_root(&dir, Some("v1.2.0")).unwrap(),
+            dir.join("basic-rust.rf-1.2.0")
+        );
+
+        fs::create_dir_all(dir.join("other")).unwrap();
+        assert!(p.find_root(&dir, Some("v1.2.0")).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prefixed_root() {
+        let dir = env::temp_dir().join(format!(
+            "reframe_test_prefixed_root_{}",
+            util::get_current_time_millis()
+        ));
+        fs::create_dir_all(dir.join("owner-repo-1a2b3c")).unwrap();
+        let p = provider_for("bitbucket:owner/repo").unwrap();
+        assert_eq!(
+            p.find_root(&dir, None).unwrap(),
+            dir.join("owner-repo-1a2b3c")
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_http_archive() {
+        let zip = make_zip(&[
+            ("template/Reframe.toml", "[project]\nname = \"x\"\n"),
+            ("template/src/main.rs", "fn main() {}\n"),
+        ]);
+        let url = format!("{}/template.zip", serve(zip));
+        let cache = temp_cache("fetch");
+
+        let source = Source::parse(&url).unwrap();
+        let root = source.fetch(None, &opts(&cache, false)).await.unwrap().root;
+        assert!(root.ends_with("template"));
+        assert!(root.join("Reframe.toml").exists());
+        assert!(root.join("src/main.rs").exists());
+
+        let _ = fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_http_tar_gz_archive() {
+        let tar_gz = make_tar_gz(&[("Reframe.toml", "[project]\nname = \"x\"\n")]);
+        let url = format!("{}/t.tgz", serve(tar_gz));
+        let cache = temp_cache("fetch_tgz");
+
+        let source = Source::parse(&url).unwrap();
+        let root = source.fetch(None, &opts(&cache, false)).await.unwrap().root;
+        assert!(root.join("Reframe.toml").exists());
+
+        let _ = fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_local_archive() {
+        let cache = temp_cache("local_archive");
+        fs::create_dir_all(cache.root()).unwrap();
+
+        let tar_gz = make_tar_gz(&[("t/Reframe.toml", "[project]\nname = \"x\"\n")]);
+        let path = cache.root().join("template.tar.gz");
+        fs::write(&path, tar_gz).unwrap();
+        let spec = SourceSpec::parse(&path.to_string_lossy()).unwrap();
+        assert!(matches!(spec.source, Source::Archive(_)));
+        let root = spec
+            .source
+            .fetch(None, &opts(&cache, false))
+            .await
+            .unwrap()
+            .root;
+        assert!(root.join("Reframe.toml").exists());
+
+        // detected by magic bytes regardless of the name
+        let zip = make_zip(&[("Reframe.toml", "[project]\nname = \"x\"\n")]);
+        let path = cache.root().join("template.bin");
+        fs::write(&path, zip).unwrap();
+        let root = Source::parse(&path.to_string_lossy())
+            .unwrap()
+            .fetch(None, &opts(&cache, false))
+            .await
+            .unwrap()
+            .root;
+        assert!(root.join("Reframe.toml").exists());
+
+        assert!(matches!(Source::parse("-").unwrap(), Source::Stdin));
+
+        let _ = fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_pinned_archive() {
+        let zip = make_zip(&[("Reframe.toml", "[project]\nname = \"x\"\n")]);
+        let url = format!("{}/t.zip", serve(zip));
+        let cache = temp_cache("fetch_pinned");
+        let source = Source::parse(&url).unwrap();
+
+        let wrong = "0".repeat(64);
+        let pinned = |sha256| FetchOptions {
+            sha256: Some(sha256),
+            ..opts(&cache, false)
+        };
+        let err = source.fetch(None, &pinned(&wrong)).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("checksum mismatch"));
+        assert!(cache.list().unwrap().is_empty());
+
+        let digest = source
+            .fetch(None, &opts(&cache, false))
+            .await
+            .unwrap()
+            .sha256
+            .unwrap();
+        let fetched = source.fetch(None, &pinned(&digest)).await.unwrap();
+        assert_eq!(fetched.sha256.as_ref(), Some(&digest));
+        assert!(fetched.root.join("Reframe.toml").exists());
+
+        // cached copy is verified too
+        let err = source
+            .fetch(
+                None,
+                &FetchOptions {
+                    offline: true,
+                    ..pinned(&wrong)
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+
+        let err = Source::parse("file:///tmp/x.git")
+            .unwrap()
+            .fetch(None, &pinned(&digest))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let _ = fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_tries_archive_candidates() {
+        struct Candidates(String);
+        impl SourceProvider for Candidates {
+            fn name(&self) -> &'static str {
+                "test"
+            }
+            fn location(&self) -> String {
+                self.0.to_owned()
+            }
+            fn host(&self) -> String {
+                "127.0.0.1".to_string()
+            }
+            fn archive_url(&self, reference: Option<&str>) -> String {
+                format!("{}/x.rf/{}.zip", self.0, reference.unwrap_or("HEAD"))
+            }
+            fn archive_urls(&self, reference: Option<&str>) -> Vec<String> {
+                vec![
+                    self.archive_url(reference),
+                    format!("{}/x/{}.zip", self.0, reference.unwrap_or("HEAD")),
+                ]
+            }
+        }
+
+        let zip = make_zip(&[("x-1.0/Reframe.toml", "[project]\nname = \"x\"\n")]);
+        let base = serve_with(move |req| {
+            if req.starts_with("GET /x/v1.0.zip ") {
+                Response::ok(zip.clone())
+            } else {
+                Response {
+                    status: 404,
+                    headers: vec![],
+                    body: b"Not Found".to_vec(),
+                }
+            }
+        });
+        let cache = temp_cache("fetch_candidates");
+        let source = Source::Remote(Box::new(Candidates(base)));
+
+        let root = source
+            .fetch(Some("v1.0"), &opts(&cache, false))
+            .await
+            .unwrap()
+            .root;
+        assert!(root.ends_with("x-1.0"));
+
+        let err = source
+            .fetch(Some("v2.0"), &opts(&cache, false))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("/x.rf/v2.0.zip"));
+
+        let _ = fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_revalidates_cache() {
+        let zip = make_zip(&[("Reframe.toml", "[project]\nname = \"x\"\n")]);
+        let downloads = Arc::new(AtomicUsize::new(0));
+        let counter = downloads.clone();
+        let base = serve_with(move |req| {
+            if req.to_lowercase().contains("if-none-match: \"v1\"") {
+                return Response {
+                    status: 304,
+                    headers: vec![],
+                    body: vec![],
+                };
+            }
+            counter.fetch_add(1, Ordering::SeqCst);
+            Response {
+                status: 200,
+                headers: vec![("ETag".to_string(), "\"v1\"".to_string())],
+                body: zip.clone(),
+            }
+        });
+        let url = format!("{}/template.zip", base);
+        let cache = temp_cache("fetch_revalidate");
+        let source = Source::parse(&url).unwrap();
+
+        assert!(source.fetch(None, &opts(&cache, true)).await.is_err());
+
+        let root = source.fetch(None, &opts(&cache, false)).await.unwrap().root;
+        assert!(root.join("Reframe.toml").exists());
+
+        let entries = cache.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].etag.as_deref(), Some("\"v1\""));
+
+        let root = source.fetch(None, &opts(&cache, false)).await.unwrap().root;
+        assert!(root.join("Reframe.toml").exists());
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+
+        let root = source.fetch(None, &opts(&cache, true)).await.unwrap().root;
+        assert!(root.join("Reframe.toml").exists());
+
+        let _ = fs::remove_dir_all(cache.root());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_private_archive() {
+        let zip = make_zip(&[("Reframe.toml", "[project]\nname = \"x\"\n")]);
+        let base = serve_with(move |req| {
+            let req = req.to_lowercase();
+            if req.contains("authorization: bearer secret") {
+                Response::ok(zip.clone())
+            } else if req.contains("authorization:") {
+                Response {
+                    status: 401,
+                    headers: vec![],
+                    body: vec![],
+                }
+            } else {
+                Response {
+                    status: 404,
+                    headers: vec![],
+                    body: vec![],
+                }
+            }
+        });
+        let url = format!("{}/private.zip", base);
+        let cache = temp_cache("fetch_private");
+        let source = Source::parse(&url).unwrap();
+
+        let err = source.fetch(None, &opts(&cache, false)).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("`[credentials]`"));
+
+        let user_config: UserConfig =
+            toml::from_str("[credentials.\"127.0.0.1\"]\ntoken = \"wrong\"\n").unwrap();
+        let fetch_opts = FetchOptions {
+            cache: &cache,
+            offline: false,
+            user_config: &user_config,
+            sha256: None,
+        };
+        let err = source.fetch(None, &fetch_opts).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("not authorized"));
+
+        let user_config: UserConfig =
+            toml::from_str("[credentials.\"127.0.0.1\"]\ntoken = \"secret\"\n").unwrap();
+        let fetch_opts = FetchOptions {
+            cache: &cache,
+            offline: false,
+            user_config: &user_config,
+            sha256: None,
+        };
+        let root = source.fetch(None, &fetch_opts).await.unwrap().root;
+        assert!(root.join("Reframe.toml").exists());
+
+        let _ = fs::remove_dir_all(cache.root());
+    }
+
+    #[test]
+    fn test_provider_auth() {
+        let p = provider_for("gitlab:group/repo").unwrap();
+        assert_eq!(p.token_env(), Some("GITLAB_TOKEN"));
+        assert_eq!(p.auth_header("t"), ("PRIVATE-TOKEN", "t".to_string()));
+        assert_eq!(
+            p.private_archive_urls(Some("v1")),
+            vec!["https://gitlab.com/api/v4/projects/group%2Frepo/repository/archive.zip?sha=v1"]
+        );
+
+        let p = provider_for("anvie/basic-rust").unwrap();
+        assert_eq!(p.token_env(), Some("GITHUB_TOKEN"));
+        assert_eq!(
+            p.auth_header("t"),
+            ("Authorization", "Bearer t".to_string())
+        );
+        assert_eq!(
+            p.private_archive_urls(None)[0],
+            "https://api.github.com/repos/anvie/basic-rust.rf/zipball"
+        );
+
+        let p = provider_for("https://git.example.com:8080/t.zip").unwrap();
+        assert_eq!(p.host(), "git.example.com");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_version_git() {
+        use std::process::Command;
+
+        let root = env::temp_dir().join(format!(
+            "reframe_test_resolve_git_{}",
+            util::get_current_time_millis()
+        ));
+        let work = root.join("work");
+        fs::create_dir_all(&work).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(&work)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "--quiet", "--initial-branch=master"]);
+        for tag in ["v1.0.0", "v1.2.0", "v1.3.1", "v2.0.0"] {
+            fs::write(work.join("Reframe.toml"), format!("# {}\n", tag)).unwrap();
+            git(&["add", "."]);
+            git(&["commit", "--quiet", "-m", tag]);
+            git(&["tag", tag]);
+        }
+        let bare = root.join("template.rf.git");
+        git(&["clone", "--quiet", "--bare", ".", &bare.to_string_lossy()]);
+
+        let cache = temp_cache("resolve_git");
+        let source = Source::parse(&format!("file://{}", bare.display())).unwrap();
+        let tag = source
+            .resolve_version("^1.2", &opts(&cache, false))
+            .await
+            .unwrap();
+        assert_eq!(tag, "v1.3.1");
+        let err = source
+            .resolve_version("^3", &opts(&cache, false))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("available: v1.0.0, v1.2.0"));
+
+        // offline only the cached tags are known
+        source
+            .fetch(Some("v1.2.0"), &opts(&cache, false))
+            .await
+            .unwrap();
+        let tag = source
+            .resolve_version("^1", &opts(&cache, true))
+            .await
+            .unwrap();
+        assert_eq!(tag, "v1.2.0");
+
+        let err = Source::parse(&root.to_string_lossy())
+            .unwrap()
+            .resolve_version("^1", &opts(&cache, false))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let _ = fs::remove_dir_all(cache.root());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Provider listing tags at `paths` of the local test server.
+    struct Tagged(String, Vec<&'static str>);
+    impl SourceProvider for Tagged {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+        fn location(&self) -> String {
+            self.0.to_owned()
+        }
+        fn host(&self) -> String {
+            "127.0.0.1".to_string()
+        }
+        fn archive_url(&self, reference: Option<&str>) -> String {
+            format!("{}/{}.zip", self.0, reference.unwrap_or("HEAD"))
+        }
+        fn tags_urls(&self) -> Vec<String> {
+            self.1
+                .iter()
+                .map(|path| format!("{}{}", self.0, path))
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_version_provider() {
+        let base = serve_with(|req| {
+            if req.starts_with("GET /x/tags ") {
+                Response::ok(
+                    br#"[{"name": "v0.9.0"}, {"name": "v1.1.0"}, {"name": "v1.4.2"}]"#.to_vec(),
+                )
+            } else {
+                Response {
+                    status: 404,
+                    headers: vec![],
+                    body: b"Not Found".to_vec(),
+                }
+            }
+        });
+        let cache = temp_cache("resolve_provider");
+        let source = Source::Remote(Box::new(Tagged(base, vec!["/x.rf/tags", "/x/tags"])));
+        assert_eq!(
+            source
+                .resolve_version("~1.1", &opts(&cache, false))
+                .await
+                .unwrap(),
+            "v1.1.0"
+        );
+        assert_eq!(
+            source
+                .resolve_version(">=1.0", &opts(&cache, false))
+                .await
+                .unwrap(),
+            "v1.4.2"
+        );
+
+        let bitbucket = serde_json::json!({"values": [{"name": "v1.0.0"}], "pagelen": 100});
+        assert_eq!(tag_names(&bitbucket), vec!["v1.0.0"]);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_version_paged() {
+        // the highest match is on the last page only
+        let base = serve_with(|req| {
+            let path = req.split(' ').nth(1).unwrap_or("");
+            let host = req
+                .lines()
+                .find_map(|line| line.strip_prefix("host: ").or(line.strip_prefix("Host: ")))
+                .unwrap_or("")
+                .trim();
+            let tags = |names: &[&str]| {
+                names
+                    .iter()
+                    .map(|name| format!(r#"{{"name": "{}"}}"#, name))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            let page = |names: &[&str], headers: Vec<(String, String)>| Response {
+                status: 200,
+                headers,
+                body: format!("[{}]", tags(names)).into_bytes(),
+            };
+            match path {
+                // GitHub, `Link` header
+                "/github/tags?per_page=2" => page(
+                    &["v1.2.0", "v1.0.0"],
+                    vec![(
+                        "Link".to_string(),
+                        format!(
+                            r#"<http://{}/github/tags?per_page=2&page=2>; rel="next", <http://{}/github/tags?per_page=2&page=2>; rel="last""#,
+                            host, host
+                        ),
+                    )],
+                ),
+                "/github/tags?per_page=2&page=2" => page(&["v1.9.0"], vec![]),
+                // GitLab, `X-Next-Page` header
+                "/gitlab/tags?per_page=2" => page(
+                    &["v1.2.0", "v1.0.0"],
+                    vec![("X-Next-Page".to_string(), "2".to_string())],
+                ),
+                "/gitlab/tags?per_page=2&page=2" => page(
+                    &["v1.9.0"],
+                    vec![("X-Next-Page".to_string(), "".to_string())],
+                ),
+                // Gitea, page numbers
+                "/gitea/tags?limit=2&page=1" => page(&["v1.2.0", "v1.0.0"], vec![]),
+                "/gitea/tags?limit=2&page=2" => page(&["v1.9.0"], vec![]),
+                // Bitbucket, `next` field
+                "/bitbucket/tags" => Response::ok(
+                    format!(
+                        r#"{{"values": [{}], "next": "http://{}/bitbucket/tags?page=2"}}"#,
+                        tags(&["v1.2.0", "v1.0.0"]),
+                        host
+                    )
+                    .into_bytes(),
+                ),
+                "/bitbucket/tags?page=2" => {
+                    Response::ok(format!(r#"{{"values": [{}]}}"#, tags(&["v1.9.0"])).into_bytes())
+                }
+                _ => Response {
+                    status: 404,
+                    headers: vec![],
+                    body: b"Not Found".to_vec(),
+                },
+            }
+        });
+        let cache = temp_cache("resolve_paged");
+        for path in [
+            "/github/tags?per_page=2",
+            "/gitlab/tags?per_page=2",
+            "/gitea/tags?limit=2&page=1",
+            "/bitbucket/tags",
+        ] {
+            let source = Source::Remote(Box::new(Tagged(base.to_owned(), vec![path])));
+            let mut tags = source.tags(&opts(&cache, false)).await.unwrap();
+            tags.sort();
+            assert_eq!(tags, vec!["v1.0.0", "v1.2.0", "v1.9.0"], "{}", path);
+            assert_eq!(
+                source
+                    .resolve_version("^1.2", &opts(&cache, false))
+                    .await
+                    .unwrap(),
+                "v1.9.0"
+            );
+        }
+    }
+}
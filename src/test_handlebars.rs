@@ -1,9 +1,5 @@
-
-
 use crate::core::*;
 
-
-
 fn build_config(name: &str) -> Config {
     Config {
         reframe: ReframeConfig {
@@ -18,6 +14,7 @@ fn build_config(name: &str) -> Config {
             ignore_dirs: None,
             ignore_files: None,
             finish_text: None,
+            name_rules: Default::default(),
         },
         param: vec![],
         presents: vec![],
@@ -162,5 +159,3 @@ function doSomething(){
     .unwrap();
     assert_eq!(output, expected4);
 }
-
-
@@ -0,0 +1,89 @@
+//! Helpers shared by unit tests.
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+use zip::write::{FileOptions, ZipWriter};
+
+/// Response of the local test server: status code, extra headers and body.
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok(body: Vec<u8>) -> Self {
+        Response {
+            status: 200,
+            headers: vec![],
+            body,
+        }
+    }
+}
+
+/// Serve requests on a local port with `handler`, returns the base url.
+///
+/// The handler receives the raw request head (request line and headers).
+pub fn serve_with<F>(handler: F) -> String
+where
+    F: Fn(&str) -> Response + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut buf = [0; 8192];
+            let n = stream.read(&mut buf).unwrap_or(0);
+            let req = String::from_utf8_lossy(&buf[..n]).to_string();
+            let resp = handler(&req);
+            let mut head = format!(
+                "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n",
+                resp.status,
+                resp.body.len()
+            );
+            for (k, v) in resp.headers.iter() {
+                head.push_str(&format!("{}: {}\r\n", k, v));
+            }
+            head.push_str("\r\n");
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&resp.body);
+        }
+    });
+    format!("http://{}", addr)
+}
+
+/// Serve `body` for every request, returns the base url.
+pub fn serve(body: Vec<u8>) -> String {
+    serve_with(move |_| Response::ok(body.clone()))
+}
+
+pub fn make_zip(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut zw = ZipWriter::new(io::Cursor::new(vec![]));
+    for (name, content) in entries {
+        zw.start_file(*name, FileOptions::default()).unwrap();
+        zw.write_all(content.as_bytes()).unwrap();
+    }
+    zw.finish().unwrap().into_inner()
+}
+
+pub fn make_tar_gz(entries: &[(&str, &str)]) -> Vec<u8> {
+    let gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    let mut tw = tar::Builder::new(gz);
+    for (name, content) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tw.append_data(&mut header, name, content.as_bytes())
+            .unwrap();
+    }
+    tw.into_inner().unwrap().finish().unwrap()
+}
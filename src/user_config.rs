@@ -0,0 +1,197 @@
+use log::debug;
+use serde::Deserialize;
+
+use crate::archive::Limits;
+
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Access token for a host.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Credential {
+    pub token: String,
+}
+
+/// User level configuration, read from `~/.config/reframe/config.toml`, eg:
+///
+/// ```toml
+/// branch = "main"
+/// registry = "https://git.example.com/ours/templates/raw/main/registry.toml"
+/// color = false
+/// quiet = true
+/// remember_answers = false
+///
+/// [defaults]
+/// author_name = "Robin"
+///
+/// [aliases]
+/// svc = "ourorg/rust-service"
+///
+/// [credentials."git.example.com"]
+/// token = "secret"
+///
+/// [extract]
+/// max_total_size = 104857600
+/// max_files = 5000
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct UserConfig {
+    #[serde(default = "HashMap::new")]
+    pub credentials: HashMap<String, Credential>,
+    #[serde(default)]
+    pub extract: Limits,
+    /// Registries listed by `--list`, in order, replacing the default registry.
+    #[serde(default)]
+    pub registries: Vec<String>,
+    /// Registry used instead of the built-in one when no registries listed.
+    pub registry: Option<String>,
+    /// Branch used when source has no reference.
+    pub branch: Option<String>,
+    /// Default answers of params by key, overriding the template defaults.
+    #[serde(default)]
+    pub defaults: HashMap<String, String>,
+    /// Short names of sources, eg: `svc = "ourorg/rust-service"`.
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// Colored output, `None` means auto.
+    pub color: Option<bool>,
+    /// Don't ask params, use the defaults.
+    #[serde(default)]
+    pub quiet: bool,
+    /// Offer the answers given last time as defaults, `None` means enabled.
+    pub remember_answers: Option<bool>,
+}
+
+impl UserConfig {
+    /// Path of user config file, can be overridden by `REFRAME_CONFIG` env var.
+    pub fn path() -> Option<PathBuf> {
+        if let Ok(path) = env::var("REFRAME_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        env::var("XDG_CONFIG_HOME")
+            .ok()
+            .filter(|a| !a.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+            .map(|dir| dir.join("reframe").join("config.toml"))
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let f = fs::read(path)?;
+        let rv = String::from_utf8_lossy(f.as_slice());
+        toml::from_str(&rv).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Load user config, missing file means default config.
+    pub fn load() -> io::Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => {
+                debug!("reading user config: {}", path.display());
+                Self::read(&path).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("invalid user config `{}`: {}", path.display(), e),
+                    )
+                })
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Token for `host`, `env_var` takes precedence over the credentials section.
+    pub fn token_for(&self, host: &str, env_var: Option<&str>) -> Option<String> {
+        env_var
+            .and_then(|name| env::var(name).ok())
+            .filter(|token| !token.is_empty())
+            .or_else(|| self.credentials.get(host).map(|c| c.token.to_owned()))
+    }
+
+    /// Expand alias at the start of `source`, keeping reference and sub-directory,
+    /// eg: `svc@v2` -> `ourorg/rust-service@v2`.
+    pub fn resolve_alias(&self, source: &str) -> String {
+        let end = source.find(['@', '#']).unwrap_or(source.len());
+        let end = source[..end].find("//").unwrap_or(end);
+        match self.aliases.get(&source[..end]) {
+            Some(target) => format!("{}{}", target, &source[end..]),
+            None => source.to_string(),
+        }
+    }
+
+    /// Registries in order, `registries` or the configured default registry.
+    pub fn registries(&self) -> Vec<String> {
+        if self.registries.is_empty() {
+            self.registry.iter().cloned().collect()
+        } else {
+            self.registries.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preferences() {
+        let config: UserConfig = toml::from_str(
+            r#"
+            branch = "main"
+            registry = "https://git.example.com/registry.toml"
+            quiet = true
+            remember_answers = false
+
+            [defaults]
+            author_name = "Robin"
+
+            [aliases]
+            svc = "ourorg/rust-service"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.branch.as_deref(), Some("main"));
+        assert!(config.quiet);
+        assert_eq!(config.color, None);
+        assert_eq!(config.remember_answers, Some(false));
+        assert_eq!(config.defaults["author_name"], "Robin");
+        assert_eq!(
+            config.registries(),
+            vec!["https://git.example.com/registry.toml"]
+        );
+
+        assert_eq!(config.resolve_alias("svc"), "ourorg/rust-service");
+        assert_eq!(config.resolve_alias("svc@v2"), "ourorg/rust-service@v2");
+        assert_eq!(config.resolve_alias("svc//api"), "ourorg/rust-service//api");
+        assert_eq!(config.resolve_alias("svc2"), "svc2");
+        assert_eq!(config.resolve_alias("anvie/basic-rust"), "anvie/basic-rust");
+    }
+
+    #[test]
+    fn test_token_for() {
+        let config: UserConfig = toml::from_str(
+            r#"
+            [credentials."git.example.com"]
+            token = "secret"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.token_for("git.example.com", None).as_deref(),
+            Some("secret")
+        );
+        assert_eq!(config.token_for("github.com", None), None);
+
+        env::set_var("REFRAME_TEST_TOKEN", "from-env");
+        assert_eq!(
+            config
+                .token_for("git.example.com", Some("REFRAME_TEST_TOKEN"))
+                .as_deref(),
+            Some("from-env")
+        );
+        env::remove_var("REFRAME_TEST_TOKEN");
+    }
+}
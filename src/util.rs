@@ -1,121 +1,326 @@
 #![allow(dead_code)]
 
-use lazy_static::lazy_static;
 use log::debug;
-use regex::Regex;
-use zip::ZipArchive;
+use sha2::{Digest, Sha256};
 
 use std::{
     fs::{self, File},
-    io,
+    io::{self, IsTerminal, Write},
     path::{Path, PathBuf},
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-fn extract_zip<P: AsRef<Path>>(zip_path: P, out_dir: P) -> io::Result<()> {
-    let fin = File::open(&zip_path).unwrap_or_else(|e| {
-        panic!(
-            "Cannot open zip file `{}`. {}",
-            zip_path.as_ref().display(),
-            e
-        )
-    });
-    let mut archive = ZipArchive::new(fin)?;
-    for i in 0..archive.len() {
-        let mut file = archive
-            .by_index(i)
-            .unwrap_or_else(|e| panic!("Cannot get zip entry index `{}`: {}", i, e));
-        let outpath = out_dir.as_ref().join(file.mangled_name());
-
-        {
-            let comment = file.comment();
-            if !comment.is_empty() {
-                println!("File {} comment: {}", i, comment);
-            }
+/// Cache validators of downloaded content.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Failure of a single download attempt, transient ones are retried.
+struct DownloadFailure {
+    error: io::Error,
+    transient: bool,
+}
+
+impl DownloadFailure {
+    fn fatal(error: io::Error) -> Self {
+        DownloadFailure {
+            error,
+            transient: false,
         }
+    }
 
-        if (&*file.name()).ends_with('/') {
-            debug!(
-                "File {} extracted to \"{}\"",
-                i,
-                outpath.as_path().display()
-            );
-            fs::create_dir_all(&outpath)?;
-        } else {
-            debug!(
-                "File {} extracted to \"{}\" ({} bytes)",
-                i,
-                outpath.as_path().display(),
-                file.size()
-            );
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(&p)?;
-                }
-            }
-            let mut outfile = fs::File::create(&outpath)?;
-            io::copy(&mut file, &mut outfile)?;
+    fn transient(error: io::Error) -> Self {
+        DownloadFailure {
+            error,
+            transient: true,
+        }
+    }
+}
+
+/// Download progress printed to stderr when it is a terminal.
+struct Progress {
+    enabled: bool,
+    total: Option<u64>,
+    done: u64,
+    last_print: Instant,
+}
+
+impl Progress {
+    fn new(total: Option<u64>) -> Self {
+        Progress {
+            enabled: io::stderr().is_terminal(),
+            total,
+            done: 0,
+            last_print: Instant::now(),
+        }
+    }
+
+    fn add(&mut self, n: usize) {
+        self.done += n as u64;
+        if self.enabled && self.last_print.elapsed() >= Duration::from_millis(100) {
+            self.print();
+            self.last_print = Instant::now();
         }
+    }
 
-        // Get and Set permissions
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
+    fn print(&self) {
+        let line = match self.total {
+            Some(total) if total > 0 => format!(
+                " Downloading {} / {} ({}%)",
+                format_size(self.done),
+                format_size(total),
+                self.done * 100 / total
+            ),
+            _ => format!(" Downloading {}", format_size(self.done)),
+        };
+        eprint!("\r{:<50}", line);
+        let _ = io::stderr().flush();
+    }
 
-            if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
-            }
+    fn finish(&self) {
+        if self.enabled {
+            self.print();
+            eprintln!();
         }
     }
-    Ok(())
 }
 
-/// Download data from internet.
+/// Human readable size, eg: `1.5 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn http_client() -> io::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(concat!("reframe/", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .map_err(io::Error::other)
+}
+
+/// Content written by [`download`].
+#[derive(Debug, Clone)]
+pub struct Downloaded {
+    pub validators: Validators,
+    /// Hex encoded SHA-256 digest of the content.
+    pub sha256: String,
+}
+
+/// Download data from internet into `out_path` when the content differs from `validators`.
+///
+/// Returns the new content info, or `None` when the server
+/// replied not modified and `out_path` is left untouched.
+/// Content not matching `expected_sha256` is refused and never written to `out_path`.
+/// Connection errors, timeouts and server errors are retried with backoff.
 pub async fn download<P: AsRef<Path>>(
     url: &str,
-    out_dir: P,
-    out_file_name: &str,
-) -> io::Result<()> {
-    fs::create_dir_all(&out_dir)?;
-
-    let out_path = out_dir.as_ref().join(out_file_name);
+    out_path: P,
+    validators: &Validators,
+    headers: &[(&str, String)],
+    expected_sha256: Option<&str>,
+) -> io::Result<Option<Downloaded>> {
+    debug!("downloading {} ...", url);
+
+    let client = http_client()?;
+
+    let mut attempt = 1;
+    loop {
+        let rv = download_once(
+            &client,
+            url,
+            out_path.as_ref(),
+            validators,
+            headers,
+            expected_sha256,
+        )
+        .await;
+        match rv {
+            Err(failure) if failure.transient && attempt < DOWNLOAD_ATTEMPTS => {
+                let delay = RETRY_BACKOFF * 2u32.pow(attempt - 1);
+                eprintln!(
+                    " {}, retrying in {:.1}s ...",
+                    failure.error,
+                    delay.as_secs_f32()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            rv => return rv.map_err(|failure| failure.error),
+        }
+    }
+}
 
-    {
-        let mut fw = File::create(&out_path)?;
+async fn download_once(
+    client: &reqwest::Client,
+    url: &str,
+    out_path: &Path,
+    validators: &Validators,
+    headers: &[(&str, String)],
+    expected_sha256: Option<&str>,
+) -> Result<Option<Downloaded>, DownloadFailure> {
+    let mut req = client.get(url);
+    for (name, value) in headers {
+        req = req.header(*name, value);
+    }
+    if let Some(etag) = validators.etag.as_ref() {
+        req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = validators.last_modified.as_ref() {
+        req = req.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+    }
 
-        debug!("downloading {} ...", url);
+    let mut resp = req.send().await.map_err(|e| {
+        let error = io::Error::other(format!("cannot download: {} (error: {})", url, e));
+        if e.is_connect() || e.is_timeout() {
+            DownloadFailure::transient(error)
+        } else {
+            DownloadFailure::fatal(error)
+        }
+    })?;
 
-        let resp = reqwest::get(url).await.map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("cannot clone from github: {} (error: {})", url, e),
-            )
-        })?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_MODIFIED {
+        debug!("not modified: {}", url);
+        return Ok(None);
+    }
 
-        let mut content = std::io::Cursor::new(resp.bytes().await.map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("cannot read data from github: {} (error: {})", url, e),
-            )
-        })?);
+    match status {
+        reqwest::StatusCode::NOT_FOUND => Err(DownloadFailure::fatal(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("not found: {} (HTTP 404)", url),
+        )))?,
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+            Err(DownloadFailure::fatal(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("not authorized: {} (HTTP {})", url, status.as_u16()),
+            )))?
+        }
+        status if !status.is_success() => {
+            let error = io::Error::other(format!(
+                "cannot download: {} (HTTP {})",
+                url,
+                status.as_u16()
+            ));
+            if status.is_server_error()
+                || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::REQUEST_TIMEOUT
+            {
+                Err(DownloadFailure::transient(error))?
+            } else {
+                Err(DownloadFailure::fatal(error))?
+            }
+        }
+        _ => (),
+    }
 
-        std::io::copy(&mut content, &mut fw)?;
+    let header = |name: reqwest::header::HeaderName| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let new_validators = Validators {
+        etag: header(reqwest::header::ETAG),
+        last_modified: header(reqwest::header::LAST_MODIFIED),
+    };
+
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent).map_err(DownloadFailure::fatal)?;
     }
 
-    // extract zip file
-    let zip_file = out_path.clone();
+    // stream into a temporary file, so failed download never replaces `out_path`
+    let mut part_path = out_path.as_os_str().to_owned();
+    part_path.push(".part");
+    let part_path = PathBuf::from(part_path);
+    let mut fw = File::create(&part_path).map_err(DownloadFailure::fatal)?;
+
+    let written: Result<String, DownloadFailure> = async {
+        let mut hasher = Sha256::new();
+        let mut progress = Progress::new(resp.content_length());
+        loop {
+            let chunk = match tokio::time::timeout(READ_TIMEOUT, resp.chunk()).await {
+                Ok(Ok(Some(chunk))) => chunk,
+                Ok(Ok(None)) => break,
+                Ok(Err(e)) => Err(DownloadFailure::transient(io::Error::other(format!(
+                    "cannot read data from: {} (error: {})",
+                    url, e
+                ))))?,
+                Err(_) => Err(DownloadFailure::transient(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("timed out reading data from: {}", url),
+                )))?,
+            };
+            fw.write_all(&chunk).map_err(DownloadFailure::fatal)?;
+            hasher.update(&chunk);
+            progress.add(chunk.len());
+        }
+        progress.finish();
 
-    debug!(
-        "extracting `{}` to `{}` ...",
-        &zip_file.display(),
-        &out_dir.as_ref().display()
-    );
+        drop(fw);
 
-    if extract_zip(zip_file, out_dir.as_ref().to_path_buf()).is_err() {
-        Err(io::Error::from(io::ErrorKind::NotFound))?;
+        let sha256 = format!("{:x}", hasher.finalize());
+        if let Some(expected) = expected_sha256 {
+            if !expected.eq_ignore_ascii_case(&sha256) {
+                Err(DownloadFailure::fatal(checksum_mismatch(
+                    url, expected, &sha256,
+                )))?;
+            }
+        }
+
+        fs::rename(&part_path, out_path).map_err(DownloadFailure::fatal)?;
+        Ok(sha256)
+    }
+    .await;
+    // never leave partial content behind, the next attempt starts over
+    if written.is_err() {
+        let _ = fs::remove_file(&part_path);
     }
+    let sha256 = written?;
 
-    Ok(())
+    Ok(Some(Downloaded {
+        validators: new_validators,
+        sha256,
+    }))
+}
+
+pub fn checksum_mismatch(location: &str, expected: &str, actual: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "checksum mismatch for `{}`: expected sha256 {}, got {}",
+            location, expected, actual
+        ),
+    )
+}
+
+/// Hex encoded SHA-256 digest of file content.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Whether `s` looks like a hex encoded SHA-256 digest.
+pub fn is_sha256(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
 }
 
 #[inline]
@@ -192,13 +397,7 @@ pub fn compare_version(version_a: &str, version_b: &str) -> i32 {
 
     let rv = v_a - v_b;
 
-    if rv > 1 {
-        1
-    } else if rv < -1 {
-        -1
-    } else {
-        rv
-    }
+    rv.clamp(-1, 1)
 }
 
 /// Mendapatkan timestamp waktu terkini.
@@ -210,51 +409,86 @@ pub fn get_current_time_millis() -> u128 {
     u128::from(since_epoch.as_secs()) * 1000 + u128::from(since_epoch.subsec_millis())
 }
 
-const SOURCES_URL: &'static str =
-    "https://raw.githubusercontent.com/Ansvia/reframe/master/SOURCES.md";
-lazy_static! {
-    static ref RE_REFRAME_URL: Regex =
-        Regex::new(r"\* \[(\w+/[a-zA-Z0-9-.]*?)]\(https://github\.com/.*?.rf\) - (.*)").unwrap();
+/// Get text content at `url`.
+pub async fn get_text(url: &str, headers: &[(&str, String)]) -> io::Result<String> {
+    get_page(url, headers).await.map(|(text, _)| text)
 }
 
-pub async fn get_available_sources() -> io::Result<Vec<(String, String)>> {
-    let a = get_current_time_millis();
-    let url = format!("{}?a={}", SOURCES_URL, a);
-    log::debug!("querying sources from: {}", url);
-    let resp = reqwest::get(url).await.map_err(|e| {
-        io::Error::new(
+/// Get text content of paged API at `url` with the url of the next page if any,
+/// taken from the `Link` header or GitLab `X-Next-Page` header.
+pub async fn get_page(
+    url: &str,
+    headers: &[(&str, String)],
+) -> io::Result<(String, Option<String>)> {
+    let mut req = http_client()?.get(url).timeout(READ_TIMEOUT);
+    for (name, value) in headers {
+        req = req.header(*name, value);
+    }
+    let resp = req
+        .send()
+        .await
+        .map_err(|e| io::Error::other(format!("cannot download: {} (error: {})", url, e)))?;
+
+    match resp.status() {
+        reqwest::StatusCode::NOT_FOUND => Err(io::Error::new(
             io::ErrorKind::NotFound,
-            format!("Cannot query sources: {} (error: {})", SOURCES_URL, e),
-        )
-    })?;
+            format!("not found: {}", url),
+        ))?,
+        status if !status.is_success() => Err(io::Error::other(format!(
+            "cannot download: {} (HTTP {})",
+            url,
+            status.as_u16()
+        )))?,
+        _ => (),
+    }
 
-    let text = resp.text().await.map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!("Cannot read sources from: {} (error: {})", SOURCES_URL, e),
-        )
-    })?;
+    let header = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let next = header("link")
+        .and_then(|link| next_link(&link))
+        .or_else(|| {
+            header("x-next-page")
+                .filter(|page| !page.trim().is_empty())
+                .map(|page| with_query_param(url, "page", page.trim()))
+        });
+
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| io::Error::other(format!("cannot read data from: {} (error: {})", url, e)))?;
+    Ok((text, next))
+}
 
-    log::debug!("sources: {}", text);
+/// Url of `rel="next"` in `Link` header, eg: `<https://x/tags?page=2>; rel="next"`.
+fn next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        params
+            .split(';')
+            .any(|p| p.trim() == "rel=\"next\"")
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
 
-    let texts = text
-        .split("\n")
-        .filter(|s| RE_REFRAME_URL.is_match(s))
-        .map(|s| match RE_REFRAME_URL.captures(s) {
-            Some(x) => Some((x.get(1), x.get(2))),
-            None => None,
-        })
-        .flatten()
-        .map(|(a, b)| {
-            (
-                a.map(|a| a.as_str()).unwrap_or(""),
-                b.map(|b| b.as_str()).unwrap_or(""),
-            )
-        })
-        .map(|(name, description)| (name.to_string(), description.to_string()))
+/// Set query param `key` of `url` to `value`.
+pub fn with_query_param(url: &str, key: &str, value: &str) -> String {
+    let (base, query) = url.split_once('?').unwrap_or((url, ""));
+    let mut params: Vec<String> = query
+        .split('&')
+        .filter(|p| !p.is_empty() && p.split('=').next() != Some(key))
+        .map(|p| p.to_string())
         .collect();
-
-    Ok(texts)
+    params.push(format!("{}={}", key, value));
+    format!("{}?{}", base, params.join("&"))
 }
 
 #[cfg(test)]
@@ -264,26 +498,23 @@ mod tests {
     #[test]
     fn test_file_pattern_match() {
         let patts = ["README.md", "*.iml", ".packages"];
-        assert_eq!(file_pattern_match("test.iml", &patts), true);
-        assert_eq!(file_pattern_match("test.txt", &patts), false);
-        assert_eq!(file_pattern_match("README.md", &patts), true);
-        assert_eq!(file_pattern_match("README.txt", &patts), false);
-        assert_eq!(file_pattern_match(".packages", &patts), true);
-        assert_eq!(file_pattern_match(".iml", &patts), false);
+        assert!(file_pattern_match("test.iml", &patts));
+        assert!(!file_pattern_match("test.txt", &patts));
+        assert!(file_pattern_match("README.md", &patts));
+        assert!(!file_pattern_match("README.txt", &patts));
+        assert!(file_pattern_match(".packages", &patts));
+        assert!(!file_pattern_match(".iml", &patts));
     }
 
     #[test]
     fn test_path_to_relative() {
         let root = "/tmp";
         assert_eq!(
-            &format!("{}", path_to_relative("/tmp/satu/dua", &root).display()),
+            &format!("{}", path_to_relative("/tmp/satu/dua", root).display()),
             "satu/dua"
         );
         assert_eq!(
-            &format!(
-                "{}",
-                path_to_relative("/tmp/satu/dua/tiga", &root).display()
-            ),
+            &format!("{}", path_to_relative("/tmp/satu/dua/tiga", root).display()),
             "satu/dua/tiga"
         );
     }
@@ -313,4 +544,122 @@ mod tests {
     fn test_get_current_time_millis() {
         assert_ne!(get_current_time_millis(), 0);
     }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let path =
+            std::env::temp_dir().join(format!("reframe_test_sha256_{}", get_current_time_millis()));
+        fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(is_sha256(&sha256_file(&path).unwrap()));
+        assert!(!is_sha256("ba7816bf"));
+        let _ = fs::remove_file(&path);
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!(
+                "reframe_test_{}_{}",
+                name,
+                get_current_time_millis()
+            ))
+            .join("source.zip")
+    }
+
+    #[tokio::test]
+    async fn test_download_retries_transient_errors() {
+        use crate::test_util::{serve_with, Response};
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let base_url = serve_with(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                Response {
+                    status: 503,
+                    headers: vec![],
+                    body: b"busy".to_vec(),
+                }
+            } else {
+                Response::ok(b"content".to_vec())
+            }
+        });
+
+        let out_path = temp_file("download_retry");
+        let url = format!("{}/t.zip", base_url);
+        download(&url, &out_path, &Validators::default(), &[], None)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&out_path).unwrap(), b"content");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        let _ = fs::remove_dir_all(out_path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_download_removes_partial_file() {
+        use std::{io::Read, net::TcpListener, thread};
+
+        // every response is cut off before the announced length
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buf = [0; 8192];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial");
+            }
+        });
+
+        let out_path = temp_file("download_partial");
+        let url = format!("http://{}/t.zip", addr);
+        let err = download(&url, &out_path, &Validators::default(), &[], None)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().starts_with("cannot read data from: "),
+            "{}",
+            err
+        );
+        assert!(!out_path.exists());
+        assert_eq!(fs::read_dir(out_path.parent().unwrap()).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(out_path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_download_reports_status() {
+        use crate::test_util::{serve_with, Response};
+
+        for (status, message) in [(410, "cannot download"), (404, "not found")] {
+            let base_url = serve_with(move |_| Response {
+                status,
+                headers: vec![],
+                body: b"<html>Gone</html>".to_vec(),
+            });
+
+            let out_path = temp_file("download_status");
+            let url = format!("{}/t.zip", base_url);
+            let err = download(&url, &out_path, &Validators::default(), &[], None)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("{}: {} (HTTP {})", message, url, status)
+            );
+            // error page never saved as archive
+            assert!(!out_path.exists());
+        }
+    }
 }
@@ -0,0 +1,77 @@
+use semver::{Version, VersionReq};
+
+use std::io;
+
+/// Check whether reference is a semver requirement rather than a branch, tag or commit,
+/// eg: `^1.2`, `~1.2.3`, `>=1.0, <2.0` or `*`.
+pub fn is_requirement(reference: &str) -> bool {
+    reference.starts_with(['^', '~', '>', '<', '=', '*'])
+}
+
+/// Version of tag like `v1.2.3`, `1.2.3` or `v1.2`, missing minor and patch are zero.
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    let s = tag.strip_prefix('v').unwrap_or(tag);
+    let core_len = s.find(['-', '+']).unwrap_or(s.len());
+    let padding = match s[..core_len].matches('.').count() {
+        0 => ".0.0",
+        1 => ".0",
+        _ => "",
+    };
+    Version::parse(&format!("{}{}{}", &s[..core_len], padding, &s[core_len..])).ok()
+}
+
+/// Tag with the highest version matching `requirement`,
+/// pre-release tags only match requirement mentioning pre-release.
+pub fn select_tag<S: AsRef<str>>(tags: &[S], requirement: &str) -> io::Result<Option<String>> {
+    let req = VersionReq::parse(requirement).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid version requirement `{}`: {}", requirement, e),
+        )
+    })?;
+    Ok(tags
+        .iter()
+        .filter_map(|tag| parse_tag(tag.as_ref()).map(|v| (v, tag.as_ref())))
+        .filter(|(v, _)| req.matches(v))
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, tag)| tag.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_tag() {
+        let tags = [
+            "v1.0.0",
+            "v1.2.0",
+            "v1.2.5",
+            "v1.10.0",
+            "v2.0.0-beta.1",
+            "1.3",
+            "latest",
+        ];
+        assert_eq!(
+            select_tag(&tags, "^1.2").unwrap().as_deref(),
+            Some("v1.10.0")
+        );
+        assert_eq!(
+            select_tag(&tags, "~1.2").unwrap().as_deref(),
+            Some("v1.2.5")
+        );
+        assert_eq!(select_tag(&tags, "=1.3").unwrap().as_deref(), Some("1.3"));
+        assert_eq!(select_tag(&tags, "*").unwrap().as_deref(), Some("v1.10.0"));
+        assert_eq!(
+            select_tag(&tags, ">=2.0.0-beta").unwrap().as_deref(),
+            Some("v2.0.0-beta.1")
+        );
+        assert_eq!(select_tag(&tags, "^3").unwrap(), None);
+        assert!(select_tag(&tags, "^x.y").is_err());
+
+        assert!(is_requirement("^1.2"));
+        assert!(is_requirement(">=1.0, <2.0"));
+        assert!(!is_requirement("v1.2.0"));
+        assert!(!is_requirement("develop"));
+    }
+}
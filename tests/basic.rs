@@ -15,6 +15,7 @@ fn build_config(name: &str) -> Config {
             ignore_dirs: None,
             ignore_files: None,
             finish_text: None,
+            name_rules: Default::default(),
         },
         param: vec![],
         presents: vec![],
@@ -60,17 +61,17 @@ fn test_if_conditional() {
     let mut param = vec![];
     param.push(p);
 
-    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]);
+    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]).unwrap();
     assert_eq!(output, expected1);
 
     param.push(Param::new("with_x".to_string(), "true".to_owned()));
-    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]);
+    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]).unwrap();
     assert_eq!(output, expected2);
 
     param.clear();
     param.push(Param::new("with_x".to_string(), "false".to_owned()));
     param.push(Param::new("db".to_string(), "mysql".to_owned()));
-    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]);
+    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]).unwrap();
     assert_eq!(output, expected3);
 }
 
@@ -100,11 +101,9 @@ fn test_if_conditional_comment_mark() {
 
     let p = Param::new("db".to_string(), "sqlite".to_owned());
 
-    let mut param = vec![];
-    param.push(p);
-    param.push(Param::new("with_x".to_string(), "false".to_owned()));
+    let param = vec![p, Param::new("with_x".to_string(), "false".to_owned())];
 
-    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]);
+    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]).unwrap();
     assert_eq!(output, expected1);
 }
 
@@ -131,15 +130,13 @@ fn test_if_conditional_sql() {
 
     let config = build_config(name);
 
-    let mut param = vec![];
-    param.push(Param::new("with_account".to_string(), "false".to_owned()));
+    let param = vec![Param::new("with_account".to_string(), "false".to_owned())];
 
-    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]);
+    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]).unwrap();
     assert_eq!(output, expected1);
 }
 
 #[test]
-#[should_panic(expected = "unclosed if conditional `# <% if param.with_x %>` at line 2")]
 fn test_unclosed_if_tag() {
     let input = r#"\
         project = "$name$";
@@ -151,5 +148,98 @@ fn test_unclosed_if_tag() {
     let config = build_config("any");
 
     let param = vec![];
-    let _ = Reframe::process_template_str(input.to_string(), &config, &param, &[]);
+    let err = Reframe::process_template_str(input.to_string(), &config, &param, &[]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unclosed if conditional `# <% if param.with_x %>` at line 3"
+    );
+
+    let input = "# <% if param.with_x %>\n# <% if param.db %>\nimport x;";
+    let err = Reframe::process_template_str(input.to_string(), &config, &param, &[]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unclosed if conditional `# <% if param.with_x %>` at line 1"
+    );
+
+    // closed on the last line
+    let input = "# <% if param.with_x %>\nimport x;\n# <% endif %>";
+    let output = Reframe::process_template_str(input.to_string(), &config, &param, &[]).unwrap();
+    assert_eq!(output, "");
+}
+
+#[test]
+fn test_stdin_archive_takes_defaults() {
+    use std::{
+        env, fs,
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    let dir = env::temp_dir().join(format!(
+        "reframe_test_stdin_{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+
+    let archive = |license: &str| {
+        let config = format!(
+            "[reframe]\nname = \"Test\"\nauthor = \"test\"\nmin_version = \"0.1.0\"\n\n\
+             [project]\nname = \"Hello\"\nversion = \"0.1.0\"\n\n\
+             [[param]]\nlicense = {{ ask = \"License?\"{} }}\n",
+            license
+        );
+        let gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        let mut tw = tar::Builder::new(gz);
+        for (name, content) in [
+            ("tpl/Reframe.toml", config.as_str()),
+            ("tpl/README.md", "$param.license$"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tw.append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        tw.into_inner().unwrap().finish().unwrap()
+    };
+    let run = |data: Vec<u8>| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_reframe"))
+            .args(["-", "-P:name=hello"])
+            .current_dir(&dir)
+            .env("REFRAME_CACHE_DIR", dir.join("cache"))
+            .env("REFRAME_DATA_DIR", dir.join("data"))
+            .env("REFRAME_CONFIG", dir.join("none.toml"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(&data).unwrap();
+        let output = child.wait_with_output().unwrap();
+        String::from_utf8_lossy(&output.stderr).to_string()
+    };
+
+    // unanswered param without default, refused instead of prompting stdin
+    let stderr = run(archive(""));
+    assert!(
+        stderr.contains("param required: `license`, preset it with -P:license=..."),
+        "{}",
+        stderr
+    );
+    assert!(!dir.join("hello").exists());
+
+    let stderr = run(archive(", default = \"MIT\""));
+    assert!(!stderr.contains("ERROR"), "{}", stderr);
+    assert_eq!(
+        fs::read_to_string(dir.join("hello/README.md"))
+            .unwrap()
+            .trim_end(),
+        "MIT"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
 }